@@ -0,0 +1,123 @@
+use std::ops::RangeInclusive;
+
+use crate::gap_buffer::GapBuffer;
+
+// A rectangular selection between two (row, column) corners. Columns may lie
+// past the end of short lines; that virtual space is padded with spaces when
+// text is inserted into it.
+#[derive(Clone, Copy)]
+pub struct BlockSelection {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+impl BlockSelection {
+    pub fn at(position: (usize, usize)) -> Self {
+        BlockSelection {
+            anchor: position,
+            head: position,
+        }
+    }
+
+    pub fn rows(&self) -> RangeInclusive<usize> {
+        self.anchor.0.min(self.head.0)..=self.anchor.0.max(self.head.0)
+    }
+
+    // Returns the left and right column of the block.
+    pub fn columns(&self) -> (usize, usize) {
+        (self.anchor.1.min(self.head.1), self.anchor.1.max(self.head.1))
+    }
+
+    // Returns the selected part of each row. Rows ending before the block give
+    // whatever part of them lies inside it.
+    pub fn copy(&self, buffer: &GapBuffer) -> Vec<String> {
+        let (left, right) = self.columns();
+        self.rows()
+            .map(|row| {
+                let (line_start, line_length) = line_span(buffer, row);
+                (left.min(line_length)..right.min(line_length))
+                    .map(|col| buffer.get_char(line_start + col))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Replaces the block in every row with the matching entry of `lines`, or
+    // with its only entry. Returns the cursor position at the end of each
+    // insertion. The whole edit is a single undo step.
+    pub fn replace(&self, buffer: &mut GapBuffer, lines: &[&str]) -> Vec<usize> {
+        let (left, right) = self.columns();
+        let rows: Vec<usize> = self.rows().collect();
+        let line_for = |i: usize| if lines.len() == 1 { lines[0] } else { lines.get(i).copied().unwrap_or("") };
+
+        buffer.begin_undo_group();
+        // Edit bottom-up so the rows above keep their indices.
+        for (i, &row) in rows.iter().enumerate().rev() {
+            let text = line_for(i);
+            let (line_start, line_length) = line_span(buffer, row);
+            if line_length < left {
+                if text.is_empty() {
+                    continue;
+                }
+                buffer.insert_str(line_start + line_length, &" ".repeat(left - line_length));
+            }
+            buffer.delete_range(line_start + left, line_start + right.min(line_length.max(left)));
+            buffer.insert_str(line_start + left, text);
+        }
+        buffer.end_undo_group();
+
+        rows.iter()
+            .enumerate()
+            .map(|(i, &row)| buffer.get_index_at_position(row, left + line_for(i).chars().count()))
+            .collect()
+    }
+
+    // Deletes the block, or the character before a zero-width block, in every
+    // row. Returns the resulting cursor positions.
+    pub fn delete_backward(&self, buffer: &mut GapBuffer) -> Vec<usize> {
+        let (left, right) = self.columns();
+        if left != right {
+            return self.replace(buffer, &[""]);
+        }
+        if left == 0 {
+            return self.cursor_positions(buffer, left);
+        }
+        self.delete_column(buffer, left - 1);
+        self.cursor_positions(buffer, left - 1)
+    }
+
+    // Deletes the block, or the character after a zero-width block, in every
+    // row. Returns the resulting cursor positions.
+    pub fn delete_forward(&self, buffer: &mut GapBuffer) -> Vec<usize> {
+        let (left, right) = self.columns();
+        if left != right {
+            return self.replace(buffer, &[""]);
+        }
+        self.delete_column(buffer, left);
+        self.cursor_positions(buffer, left)
+    }
+
+    fn delete_column(&self, buffer: &mut GapBuffer, column: usize) {
+        buffer.begin_undo_group();
+        for row in self.rows().rev() {
+            let (line_start, line_length) = line_span(buffer, row);
+            if column < line_length {
+                buffer.delete_range(line_start + column, line_start + column + 1);
+            }
+        }
+        buffer.end_undo_group();
+    }
+
+    fn cursor_positions(&self, buffer: &GapBuffer, column: usize) -> Vec<usize> {
+        self.rows()
+            .map(|row| buffer.get_index_at_position(row, column))
+            .collect()
+    }
+}
+
+// Returns the index where `row` starts and its length, excluding the line break.
+fn line_span(buffer: &GapBuffer, row: usize) -> (usize, usize) {
+    let line_start = buffer.get_index_at_position(row, 0);
+    let line_end = buffer.get_index_at_position(row, usize::MAX);
+    (line_start, line_end - line_start)
+}
@@ -0,0 +1,98 @@
+use crate::{
+    gap_buffer::GapBuffer,
+    motions,
+    selection::{Edit, Selection},
+};
+
+const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: &[char] = &['"', '\'', '`'];
+
+// The character that closes `c`, if it opens a bracket or quote.
+fn closer_of(c: char) -> Option<char> {
+    if QUOTES.contains(&c) {
+        return Some(c);
+    }
+    PAIRS.iter().find(|&&(open, _)| open == c).map(|&(_, close)| close)
+}
+
+fn is_closer(c: char) -> bool {
+    QUOTES.contains(&c) || PAIRS.iter().any(|&(_, close)| close == c)
+}
+
+// Finds the bracket matching the one at `index`, skipping over the nested
+// brackets of the same kind.
+pub fn find_match(buffer: &GapBuffer, index: usize) -> Option<usize> {
+    let c = buffer.get_char(index);
+    let last = buffer.length() - 1;
+    if let Some(&(open, close)) = PAIRS.iter().find(|&&(open, _)| open == c) {
+        let mut depth = 0;
+        for i in index + 1..last {
+            match buffer.get_char(i) {
+                other if other == open => depth += 1,
+                other if other == close && depth == 0 => return Some(i),
+                other if other == close => depth -= 1,
+                _ => {}
+            }
+        }
+    } else if let Some(&(open, close)) = PAIRS.iter().find(|&&(_, close)| close == c) {
+        let mut depth = 0;
+        for i in (0..index).rev() {
+            match buffer.get_char(i) {
+                other if other == close => depth += 1,
+                other if other == open && depth == 0 => return Some(i),
+                other if other == open => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+// The bracket at the cursor, or else right before it, and the bracket matching it.
+pub fn pair_at(buffer: &GapBuffer, cursor: usize) -> Option<(usize, usize)> {
+    [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter(|&index| index < buffer.length() - 1)
+        .find_map(|index| Some((index, find_match(buffer, index)?)))
+}
+
+// The edit typing `c` makes at a selection if it is a bracket or quote. An
+// opener wraps the selection, or adds its closer when nothing but blanks or
+// punctuation follows. A closer typed right before the same one moves over it.
+pub fn type_char(buffer: &GapBuffer, selection: &Selection, c: char) -> Option<Edit> {
+    if !selection.is_empty() {
+        let closer = closer_of(c)?;
+        let selected: String = (selection.start()..selection.end()).map(|index| buffer.get_char(index)).collect();
+        let text = format!("{}{}{}", c, selected, closer);
+        let length = text.chars().count();
+        return Some((selection.start(), selection.end(), text, length));
+    }
+
+    let cursor = selection.head;
+    let next = buffer.get_char(cursor);
+    if is_closer(c) && next == c {
+        return Some((cursor, cursor, String::new(), 1));
+    }
+    let closer = closer_of(c)?;
+    let at_end = cursor == buffer.length() - 1;
+    if !(at_end || next.is_whitespace() || is_closer(next) || ",;:.".contains(next)) {
+        return None;
+    }
+    // A quote right after a word is more likely an apostrophe.
+    let previous = cursor.checked_sub(1).map(|index| buffer.get_char(index));
+    if QUOTES.contains(&c) && previous.is_some_and(|previous| motions::is_word_char(previous) || previous == c) {
+        return None;
+    }
+    Some((cursor, cursor, format!("{}{}", c, closer), 1))
+}
+
+// Backspace between an empty pair deletes both halves.
+pub fn delete_pair(buffer: &GapBuffer, selection: &Selection) -> Option<Edit> {
+    let cursor = selection.head;
+    if !selection.is_empty() || cursor == 0 || cursor == buffer.length() - 1 {
+        return None;
+    }
+    let closer = closer_of(buffer.get_char(cursor - 1))?;
+    (buffer.get_char(cursor) == closer).then(|| (cursor - 1, cursor + 1, String::new(), 0))
+}
@@ -0,0 +1,109 @@
+use sdl2::clipboard::ClipboardUtil;
+
+use crate::{block_selection::BlockSelection, gap_buffer::GapBuffer, selection::Selections};
+
+// Copy and paste through the system clipboard. Text copied from a block
+// selection is remembered so pasting it again inserts it as a block.
+pub struct Clipboard {
+    util: ClipboardUtil,
+    block_text: Option<String>,
+}
+
+impl Clipboard {
+    pub fn new(util: ClipboardUtil) -> Self {
+        Clipboard {
+            util,
+            block_text: None,
+        }
+    }
+
+    pub fn text(&self) -> Option<String> {
+        match self.util.clipboard_text() {
+            Ok(text) if !text.is_empty() => Some(text.replace("\r\n", "\n")),
+            _ => None,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) -> bool {
+        self.block_text = None;
+        if let Err(e) = self.util.set_clipboard_text(text) {
+            eprintln!("Unable to copy to clipboard: {:?}", e);
+            return false;
+        }
+        true
+    }
+
+    // Copies the block, or every selection on its own line. Returns false if
+    // nothing was selected.
+    pub fn copy(&mut self, buffer: &GapBuffer, selections: &Selections, block: Option<&BlockSelection>) -> bool {
+        let (text, is_block) = match block {
+            Some(block) => (block.copy(buffer).join("\n"), true),
+            None => {
+                let texts = selections.selected_texts(buffer);
+                if texts.iter().all(|text| text.is_empty()) {
+                    return false;
+                }
+                (texts.join("\n"), false)
+            }
+        };
+
+        if !self.set_text(&text) {
+            return false;
+        }
+        self.block_text = is_block.then_some(text);
+        true
+    }
+
+    pub fn cut(&mut self, buffer: &mut GapBuffer, selections: &mut Selections, block: &mut Option<BlockSelection>) {
+        if !self.copy(buffer, selections, block.as_ref()) {
+            return;
+        }
+        match block.take() {
+            Some(block) => {
+                let positions = block.replace(buffer, &[""]);
+                selections.set_cursors(buffer, &positions);
+            }
+            None => selections.insert_text(buffer, ""),
+        }
+    }
+
+    // Pastes one line per row or cursor when the line count matches, otherwise
+    // the whole text at every cursor. Text copied from a block is pasted as a
+    // block below the cursor.
+    pub fn paste(&mut self, buffer: &mut GapBuffer, selections: &mut Selections, block: &mut Option<BlockSelection>) {
+        let text = match self.text() {
+            Some(text) => text,
+            None => return,
+        };
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        buffer.begin_undo_group();
+        if let Some(block) = block.take() {
+            if lines.len() == block.rows().count() {
+                let positions = block.replace(buffer, &lines);
+                selections.set_cursors(buffer, &positions);
+            } else {
+                let positions = block.replace(buffer, &[""]);
+                selections.set_cursors(buffer, &positions);
+                selections.insert_text(buffer, &text);
+            }
+        } else if self.block_text.as_ref() == Some(&text) && selections.count() == 1 {
+            let (row, col) = buffer.get_cursor_position();
+            // Add lines at the end of the text if the block reaches past it.
+            let missing_lines = (row + lines.len()).saturating_sub(buffer.get_line_count());
+            buffer.insert_str(buffer.length() - 1, &"\n".repeat(missing_lines));
+
+            let pasted = BlockSelection {
+                anchor: (row, col),
+                head: (row + lines.len() - 1, col),
+            };
+            let positions = pasted.replace(buffer, &lines);
+            selections.set_cursors(buffer, &positions[..1]);
+        } else if selections.count() > 1 && lines.len() == selections.count() {
+            selections.insert_each(buffer, &lines);
+        } else {
+            selections.insert_text(buffer, &text);
+        }
+        buffer.end_undo_group();
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    commands::{Command, COMMANDS},
+    fuzzy::fuzzy_score,
+};
+
+// The Ctrl+Shift+P palette listing the commands whose description or name
+// fuzzy-matches the query, in registry order while the query is empty.
+pub struct CommandPalette {
+    pub query: String,
+    pub scroll: usize,
+    // Indices into `COMMANDS`, best match first.
+    results: Vec<usize>,
+    selected: usize,
+    ranked_query: Option<String>,
+}
+
+impl CommandPalette {
+    pub fn new() -> Self {
+        CommandPalette {
+            query: String::new(),
+            scroll: 0,
+            results: Vec::new(),
+            selected: 0,
+            ranked_query: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.query.clear();
+        self.ranked_query = None;
+        self.update();
+    }
+
+    // Ranks the commands again if the query changed.
+    pub fn update(&mut self) {
+        if self.ranked_query.as_ref() == Some(&self.query) {
+            return;
+        }
+        let mut scored: Vec<(i32, usize)> = COMMANDS
+            .iter()
+            .enumerate()
+            .filter_map(|(index, info)| {
+                let score = fuzzy_score(&self.query, info.description).max(fuzzy_score(&self.query, info.name));
+                Some((score?, index))
+            })
+            .collect();
+        if !self.query.is_empty() {
+            scored.sort_by_key(|&(score, index)| (-score, index));
+        }
+        self.results = scored.into_iter().map(|(_, index)| index).collect();
+        self.ranked_query = Some(self.query.clone());
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.results.len().saturating_sub(1));
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.select((self.selected as isize + offset).max(0) as usize);
+    }
+
+    pub fn selected_command(&self) -> Option<Command> {
+        self.results.get(self.selected).map(|&index| COMMANDS[index].command)
+    }
+
+    // Returns the description of each listed command with its shortcut, if it has one.
+    pub fn lines(&self, shortcut: impl Fn(Command) -> Option<String>) -> Vec<(String, String)> {
+        self.results
+            .iter()
+            .map(|&index| {
+                let info = &COMMANDS[index];
+                (info.description.to_string(), shortcut(info.command).unwrap_or_default())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,257 @@
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordLeft,
+    WordRight,
+    ParagraphUp,
+    ParagraphDown,
+    LineStart,
+    LineEnd,
+    DocumentStart,
+    DocumentEnd,
+    PageUp,
+    PageDown,
+}
+
+// How Sort Lines orders the lines.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SortOptions {
+    pub ignore_case: bool,
+    // By the number each line starts with.
+    pub numeric: bool,
+    // Drops lines equal to the one before them.
+    pub unique: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CaseChange {
+    Upper,
+    Lower,
+    Title,
+    Swap,
+}
+
+// Every action of the editor that can be bound to a key or run from the
+// command palette.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Command {
+    OpenFile,
+    Save,
+    NewTab,
+    CloseTab,
+    ReopenClosedTab,
+    NextTab,
+    PreviousTab,
+    GoToFile,
+    ShowCommandPalette,
+    Quit,
+    // Moves every cursor, extending the selections if the flag is set.
+    Move(Motion, bool),
+    ExtendBlock(Motion),
+    AddCursorAbove,
+    AddCursorBelow,
+    AddNextOccurrence,
+    ClearSelectionOrQuit,
+    Newline,
+    DeleteBackward,
+    DeleteForward,
+    InsertTab,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    Find,
+    Replace,
+    FindNext,
+    FindPrevious,
+    FindInFiles,
+    Recenter,
+    ToggleSidebar,
+    FocusSidebar,
+    SplitSideBySide,
+    SplitStacked,
+    FocusNextPane,
+    ToggleVimMode,
+    ToggleEmacsKeybindings,
+    KillLine,
+    KillRegion,
+    CopyRegion,
+    Yank,
+    YankPop,
+    SetMark,
+    ClearSelection,
+    ToggleMacroRecording,
+    ReplayMacro,
+    ReplayMacroTimes,
+    SaveMacro,
+    PlayMacro,
+    Outdent,
+    ToggleIndentStyle,
+    JumpToMatchingBracket,
+    DuplicateLines,
+    DeleteLines,
+    MoveLinesUp,
+    MoveLinesDown,
+    JoinLines,
+    SortLines(SortOptions),
+    ReverseLines,
+    ChangeCase(CaseChange),
+    ToggleLineComment,
+    ToggleBlockComment,
+}
+
+pub struct CommandInfo {
+    pub command: Command,
+    // Stable identifier, e.g. for keybinding files.
+    pub name: &'static str,
+    // What the command does, as the command palette shows it.
+    pub description: &'static str,
+    pub default_keys: &'static [&'static str],
+}
+
+const fn info(
+    command: Command,
+    name: &'static str,
+    description: &'static str,
+    default_keys: &'static [&'static str],
+) -> CommandInfo {
+    CommandInfo {
+        command,
+        name,
+        description,
+        default_keys,
+    }
+}
+
+// Plain sorting, which the other sort commands each change one option of.
+const SORT: SortOptions = SortOptions {
+    ignore_case: false,
+    numeric: false,
+    unique: false,
+};
+
+// The registry of all commands, in the order the command palette lists them.
+pub static COMMANDS: &[CommandInfo] = &[
+    info(Command::OpenFile, "file.open", "File: Open...", &["Ctrl+O"]),
+    info(Command::Save, "file.save", "File: Save", &["Ctrl+S"]),
+    info(Command::NewTab, "file.new", "File: New Tab", &["Ctrl+N"]),
+    info(Command::CloseTab, "file.close", "File: Close Tab", &["Ctrl+W"]),
+    info(Command::ReopenClosedTab, "file.reopenClosed", "File: Reopen Closed Tab", &["Ctrl+Shift+T"]),
+    info(Command::GoToFile, "file.goTo", "File: Go to File...", &["Ctrl+P"]),
+    info(Command::NextTab, "tab.next", "Tabs: Next Tab", &["Ctrl+Tab"]),
+    info(Command::PreviousTab, "tab.previous", "Tabs: Previous Tab", &["Ctrl+Shift+Tab"]),
+    info(Command::Undo, "edit.undo", "Edit: Undo", &["Ctrl+Z"]),
+    info(Command::Redo, "edit.redo", "Edit: Redo", &["Ctrl+Y", "Ctrl+Shift+Z"]),
+    info(Command::Copy, "edit.copy", "Edit: Copy", &["Ctrl+C"]),
+    info(Command::Cut, "edit.cut", "Edit: Cut", &["Ctrl+X"]),
+    info(Command::Paste, "edit.paste", "Edit: Paste", &["Ctrl+V"]),
+    info(Command::Newline, "edit.newline", "Edit: Insert Line Break", &["Return", "Shift+Return"]),
+    info(Command::DeleteBackward, "edit.deleteBackward", "Edit: Delete Backward", &["Backspace", "Shift+Backspace"]),
+    info(Command::DeleteForward, "edit.deleteForward", "Edit: Delete Forward", &["Delete"]),
+    info(Command::InsertTab, "edit.tab", "Edit: Insert Tab or Indent Lines", &["Tab"]),
+    info(Command::Outdent, "edit.outdent", "Edit: Outdent Lines", &["Shift+Tab"]),
+    info(Command::ToggleIndentStyle, "edit.toggleIndentStyle", "Edit: Toggle Indenting with Tabs or Spaces", &[]),
+    info(Command::ToggleLineComment, "edit.toggleLineComment", "Edit: Toggle Line Comment", &["Ctrl+/"]),
+    info(Command::ToggleBlockComment, "edit.toggleBlockComment", "Edit: Toggle Block Comment", &["Ctrl+Shift+/"]),
+    info(Command::DuplicateLines, "lines.duplicate", "Lines: Duplicate Line or Selection", &["Ctrl+Shift+D"]),
+    info(Command::DeleteLines, "lines.delete", "Lines: Delete Line", &["Ctrl+Shift+K"]),
+    info(Command::MoveLinesUp, "lines.moveUp", "Lines: Move Line Up", &["Alt+Up"]),
+    info(Command::MoveLinesDown, "lines.moveDown", "Lines: Move Line Down", &["Alt+Down"]),
+    info(Command::JoinLines, "lines.join", "Lines: Join Lines", &["Ctrl+J"]),
+    info(Command::SortLines(SORT), "lines.sort", "Lines: Sort", &[]),
+    info(
+        Command::SortLines(SortOptions { ignore_case: true, ..SORT }),
+        "lines.sortIgnoringCase",
+        "Lines: Sort Ignoring Case",
+        &[],
+    ),
+    info(Command::SortLines(SortOptions { numeric: true, ..SORT }), "lines.sortNumerically", "Lines: Sort Numerically", &[]),
+    info(
+        Command::SortLines(SortOptions { unique: true, ..SORT }),
+        "lines.sortUnique",
+        "Lines: Sort and Remove Duplicates",
+        &[],
+    ),
+    info(Command::ReverseLines, "lines.reverse", "Lines: Reverse", &[]),
+    info(Command::ChangeCase(CaseChange::Upper), "text.upperCase", "Text: Upper Case", &[]),
+    info(Command::ChangeCase(CaseChange::Lower), "text.lowerCase", "Text: Lower Case", &[]),
+    info(Command::ChangeCase(CaseChange::Title), "text.titleCase", "Text: Title Case", &[]),
+    info(Command::ChangeCase(CaseChange::Swap), "text.swapCase", "Text: Swap Case", &[]),
+    info(Command::Find, "find.open", "Find: Find...", &["Ctrl+F"]),
+    info(Command::Replace, "find.replace", "Find: Replace...", &["Ctrl+H"]),
+    info(Command::FindNext, "find.next", "Find: Next Match", &["F3"]),
+    info(Command::FindPrevious, "find.previous", "Find: Previous Match", &["Shift+F3"]),
+    info(Command::FindInFiles, "find.inFiles", "Find: Find in Files...", &["Ctrl+Shift+F"]),
+    info(Command::AddNextOccurrence, "selection.addNextOccurrence", "Selection: Add Next Occurrence", &["Ctrl+D"]),
+    info(Command::AddCursorAbove, "selection.addCursorAbove", "Selection: Add Cursor Above", &["Ctrl+Alt+Up"]),
+    info(Command::AddCursorBelow, "selection.addCursorBelow", "Selection: Add Cursor Below", &["Ctrl+Alt+Down"]),
+    info(Command::KillLine, "edit.killLine", "Edit: Kill to End of Line", &[]),
+    info(Command::KillRegion, "edit.killRegion", "Edit: Kill Region", &[]),
+    info(Command::CopyRegion, "edit.copyRegion", "Edit: Copy Region to Kill Ring", &[]),
+    info(Command::Yank, "edit.yank", "Edit: Yank Last Kill", &[]),
+    info(Command::YankPop, "edit.yankPop", "Edit: Replace Yank with Earlier Kill", &[]),
+    info(Command::SetMark, "selection.setMark", "Selection: Set Mark", &[]),
+    info(Command::ClearSelection, "selection.clear", "Selection: Clear", &[]),
+    info(Command::ToggleMacroRecording, "macro.toggleRecording", "Macro: Start/Stop Recording", &["Ctrl+Alt+R"]),
+    info(Command::ReplayMacro, "macro.replay", "Macro: Replay", &["Ctrl+Alt+P"]),
+    info(Command::ReplayMacroTimes, "macro.replayTimes", "Macro: Replay N Times...", &[]),
+    info(Command::SaveMacro, "macro.save", "Macro: Save As...", &[]),
+    info(Command::PlayMacro, "macro.play", "Macro: Play Saved Macro...", &[]),
+    info(
+        Command::ClearSelectionOrQuit,
+        "selection.clearOrQuit",
+        "Selection: Clear, or Quit Without a Selection",
+        &["Escape"],
+    ),
+    info(Command::ExtendBlock(Motion::Left), "block.extendLeft", "Selection: Extend Block Left", &["Alt+Shift+Left"]),
+    info(Command::ExtendBlock(Motion::Right), "block.extendRight", "Selection: Extend Block Right", &["Alt+Shift+Right"]),
+    info(Command::ExtendBlock(Motion::Up), "block.extendUp", "Selection: Extend Block Up", &["Alt+Shift+Up"]),
+    info(Command::ExtendBlock(Motion::Down), "block.extendDown", "Selection: Extend Block Down", &["Alt+Shift+Down"]),
+    info(Command::Move(Motion::Left, false), "cursor.left", "Cursor: Left", &["Left"]),
+    info(Command::Move(Motion::Right, false), "cursor.right", "Cursor: Right", &["Right"]),
+    info(Command::Move(Motion::Up, false), "cursor.up", "Cursor: Up", &["Up"]),
+    info(Command::Move(Motion::Down, false), "cursor.down", "Cursor: Down", &["Down"]),
+    info(Command::Move(Motion::WordLeft, false), "cursor.wordLeft", "Cursor: Word Left", &["Ctrl+Left"]),
+    info(Command::Move(Motion::WordRight, false), "cursor.wordRight", "Cursor: Word Right", &["Ctrl+Right"]),
+    info(Command::Move(Motion::ParagraphUp, false), "cursor.paragraphUp", "Cursor: Previous Paragraph", &["Ctrl+Up"]),
+    info(Command::Move(Motion::ParagraphDown, false), "cursor.paragraphDown", "Cursor: Next Paragraph", &["Ctrl+Down"]),
+    info(Command::Move(Motion::LineStart, false), "cursor.lineStart", "Cursor: Line Start", &["Home"]),
+    info(Command::Move(Motion::LineEnd, false), "cursor.lineEnd", "Cursor: Line End", &["End"]),
+    info(Command::Move(Motion::DocumentStart, false), "cursor.documentStart", "Cursor: Start of File", &["Ctrl+Home"]),
+    info(Command::Move(Motion::DocumentEnd, false), "cursor.documentEnd", "Cursor: End of File", &["Ctrl+End"]),
+    info(Command::Move(Motion::PageUp, false), "cursor.pageUp", "Cursor: Page Up", &["PageUp"]),
+    info(Command::Move(Motion::PageDown, false), "cursor.pageDown", "Cursor: Page Down", &["PageDown"]),
+    info(Command::JumpToMatchingBracket, "cursor.matchingBracket", "Cursor: Go to Matching Bracket", &["Ctrl+M"]),
+    info(Command::Move(Motion::Left, true), "select.left", "Select: Left", &["Shift+Left"]),
+    info(Command::Move(Motion::Right, true), "select.right", "Select: Right", &["Shift+Right"]),
+    info(Command::Move(Motion::Up, true), "select.up", "Select: Up", &["Shift+Up"]),
+    info(Command::Move(Motion::Down, true), "select.down", "Select: Down", &["Shift+Down"]),
+    info(Command::Move(Motion::WordLeft, true), "select.wordLeft", "Select: Word Left", &["Ctrl+Shift+Left"]),
+    info(Command::Move(Motion::WordRight, true), "select.wordRight", "Select: Word Right", &["Ctrl+Shift+Right"]),
+    info(Command::Move(Motion::ParagraphUp, true), "select.paragraphUp", "Select: Previous Paragraph", &["Ctrl+Shift+Up"]),
+    info(Command::Move(Motion::ParagraphDown, true), "select.paragraphDown", "Select: Next Paragraph", &["Ctrl+Shift+Down"]),
+    info(Command::Move(Motion::LineStart, true), "select.lineStart", "Select: To Line Start", &["Shift+Home"]),
+    info(Command::Move(Motion::LineEnd, true), "select.lineEnd", "Select: To Line End", &["Shift+End"]),
+    info(Command::Move(Motion::DocumentStart, true), "select.documentStart", "Select: To Start of File", &["Ctrl+Shift+Home"]),
+    info(Command::Move(Motion::DocumentEnd, true), "select.documentEnd", "Select: To End of File", &["Ctrl+Shift+End"]),
+    info(Command::Move(Motion::PageUp, true), "select.pageUp", "Select: Page Up", &["Shift+PageUp"]),
+    info(Command::Move(Motion::PageDown, true), "select.pageDown", "Select: Page Down", &["Shift+PageDown"]),
+    info(Command::Recenter, "view.recenter", "View: Recenter on Cursor", &["Ctrl+L"]),
+    info(Command::ToggleSidebar, "view.toggleSidebar", "View: Toggle File Explorer", &["Ctrl+B"]),
+    info(Command::FocusSidebar, "view.focusSidebar", "View: Focus File Explorer", &["Ctrl+Shift+E"]),
+    info(Command::SplitSideBySide, "view.splitSideBySide", "View: Split Side by Side", &["Ctrl+\\"]),
+    info(Command::SplitStacked, "view.splitStacked", "View: Split Stacked", &["Ctrl+Shift+\\"]),
+    info(Command::FocusNextPane, "view.focusNextPane", "View: Focus Other Pane", &["F6"]),
+    info(Command::ToggleVimMode, "view.toggleVimMode", "View: Toggle Vim Mode", &[]),
+    info(Command::ToggleEmacsKeybindings, "view.toggleEmacsKeybindings", "View: Toggle Emacs Keybindings", &[]),
+    info(Command::ShowCommandPalette, "view.commandPalette", "View: Command Palette...", &["Ctrl+Shift+P"]),
+    info(Command::Quit, "app.quit", "Quit", &[]),
+];
+
+pub fn command_by_name(name: &str) -> Option<Command> {
+    COMMANDS.iter().find(|info| info.name == name).map(|info| info.command)
+}
@@ -0,0 +1,107 @@
+use crate::{gap_buffer::GapBuffer, motions, selection::Selections};
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+// Whether `text` is in the buffer at `index`.
+fn text_at(buffer: &GapBuffer, index: usize, text: &str) -> bool {
+    text.chars()
+        .enumerate()
+        .all(|(offset, c)| index + offset < buffer.length() - 1 && buffer.get_char(index + offset) == c)
+}
+
+// Comments out the lines with a cursor or selection on them, or uncomments
+// them if they all are comments already. The markers line up at the smallest
+// indentation among the lines, and blank lines are left alone.
+pub fn toggle_line_comments(buffer: &mut GapBuffer, selections: &mut Selections, token: &str) {
+    // The start and first non-blank character of each line.
+    let lines: Vec<(usize, usize)> = selections
+        .rows(buffer)
+        .into_iter()
+        .filter_map(|row| {
+            let start = buffer.get_index_at_position(row, 0);
+            let end = motions::line_end(buffer, start);
+            let text_start = (start..end).find(|&index| !is_blank(buffer.get_char(index)))?;
+            Some((start, text_start))
+        })
+        .collect();
+
+    let marker = format!("{} ", token);
+    let mut edits: Vec<(usize, usize, &str)> = Vec::new();
+    if lines.iter().all(|&(_, text_start)| text_at(buffer, text_start, token)) {
+        for &(_, text_start) in &lines {
+            // Takes the space after the marker along with it.
+            let removed = if text_at(buffer, text_start, &marker) { marker.chars().count() } else { token.chars().count() };
+            edits.push((text_start, removed, ""));
+        }
+    } else {
+        let indent = lines.iter().map(|&(start, text_start)| text_start - start).min().unwrap_or(0);
+        for &(start, _) in &lines {
+            edits.push((start + indent, 0, &marker));
+        }
+    }
+    selections.edit_around(buffer, &edits);
+}
+
+// Wraps each selection in a block comment, or the text of the line at an
+// empty cursor, or unwraps it if it is a block comment already. Ranges that
+// overlap, like those of two cursors on one line, are taken together.
+pub fn toggle_block_comments(buffer: &mut GapBuffer, selections: &mut Selections, (open, close): (&str, &str)) {
+    let open_marker = format!("{} ", open);
+    let close_marker = format!(" {}", close);
+    let (open_length, close_length) = (open.chars().count(), close.chars().count());
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for selection in selections.iter() {
+        let (mut start, mut end) = if selection.is_empty() {
+            let line_start = motions::line_start(buffer, selection.head);
+            (line_start, motions::line_end(buffer, line_start))
+        } else {
+            (selection.start(), selection.end())
+        };
+        // Blanks around the text stay outside the comment.
+        while start < end && buffer.get_char(start).is_whitespace() {
+            start += 1;
+        }
+        while end > start && buffer.get_char(end - 1).is_whitespace() {
+            end -= 1;
+        }
+        if start < end {
+            ranges.push((start, end));
+        }
+    }
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut edits: Vec<(usize, usize, &str)> = Vec::new();
+    for (start, end) in merged {
+        let commented = end - start >= open_length + close_length
+            && text_at(buffer, start, open)
+            && text_at(buffer, end - close_length, close);
+        if commented {
+            let inner_start = start + open_length;
+            let mut close_start = end - close_length;
+            let removed = if inner_start < close_start && buffer.get_char(inner_start) == ' ' {
+                open_length + 1
+            } else {
+                open_length
+            };
+            if close_start > start + removed && buffer.get_char(close_start - 1) == ' ' {
+                close_start -= 1;
+            }
+            edits.push((start, removed, ""));
+            edits.push((close_start, end - close_start, ""));
+        } else {
+            edits.push((start, 0, &open_marker));
+            edits.push((end, 0, &close_marker));
+        }
+    }
+    selections.edit_around(buffer, &edits);
+}
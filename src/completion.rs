@@ -0,0 +1,149 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{document::Documents, gap_buffer::GapBuffer, motions::is_word_char, settings};
+
+// The words proposed for completing the word before the cursor.
+pub struct Completion {
+    prefix: String,
+    pub words: Vec<String>,
+    pub scroll: usize,
+    selected: usize,
+}
+
+// How often each word is used in the documents other than the active one.
+// Those only change when one of them is edited, closed or opened, or another
+// document becomes active, so the words are counted again only then.
+pub struct OtherWords {
+    // What the counts were made from: the active document, and the path,
+    // version and length of each document.
+    key: (usize, Vec<(Option<PathBuf>, usize, usize)>),
+    counts: HashMap<String, usize>,
+}
+
+// Every word in `text`, with the row it is on and the index it starts at.
+fn words(text: &str) -> Vec<(usize, usize, String)> {
+    let mut words = Vec::new();
+    let mut current: Option<(usize, usize, String)> = None;
+    let mut row = 0;
+    for (index, c) in text.chars().enumerate() {
+        if is_word_char(c) {
+            current.get_or_insert_with(|| (row, index, String::new())).2.push(c);
+            continue;
+        }
+        words.extend(current.take());
+        if c == '\n' {
+            row += 1;
+        }
+    }
+    words.extend(current);
+    words
+}
+
+impl OtherWords {
+    pub fn new() -> Self {
+        OtherWords {
+            key: (0, Vec::new()),
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, documents: &Documents) -> &HashMap<String, usize> {
+        let active = documents.active_index();
+        let key = (
+            active,
+            documents
+                .iter()
+                .map(|document| (document.path.clone(), document.buffer.version(), document.buffer.length()))
+                .collect(),
+        );
+        if key != self.key {
+            self.counts.clear();
+            for (_, document) in documents.iter().enumerate().filter(|&(index, _)| index != active) {
+                for (_, _, word) in words(&document.buffer.text()) {
+                    *self.counts.entry(word).or_insert(0) += 1;
+                }
+            }
+            self.key = key;
+        }
+        &self.counts
+    }
+}
+
+impl Completion {
+    // Proposes words for the word ending at `cursor`, from the lines around it
+    // and from `others`, the words of the other open documents. Words used
+    // nearer the cursor come first, and words used more often before others as near.
+    pub fn at(buffer: &GapBuffer, cursor: usize, others: &HashMap<String, usize>) -> Option<Self> {
+        let last = buffer.length() - 1;
+        if cursor < last && is_word_char(buffer.get_char(cursor)) {
+            return None;
+        }
+        let start = (0..cursor).rev().take_while(|&index| is_word_char(buffer.get_char(index))).last()?;
+        if cursor - start < settings::COMPLETION_MIN_PREFIX {
+            return None;
+        }
+        let prefix: String = (start..cursor).map(|index| buffer.get_char(index)).collect();
+        let proposed = |word: &str| word.starts_with(&prefix) && word != prefix;
+
+        // Only the lines around the cursor are looked through, so typing stays fast in long files.
+        let mut rows_before = 0;
+        let mut window_start = cursor;
+        while window_start > 0 && rows_before <= settings::COMPLETION_SCAN_LINES {
+            window_start -= 1;
+            if buffer.get_char(window_start) == '\n' {
+                rows_before += 1;
+            }
+        }
+        let mut rows_after = 0;
+        let mut window_end = cursor;
+        while window_end < last && rows_after <= settings::COMPLETION_SCAN_LINES {
+            if buffer.get_char(window_end) == '\n' {
+                rows_after += 1;
+            }
+            window_end += 1;
+        }
+        let text: String = (window_start..window_end).map(|index| buffer.get_char(index)).collect();
+        let cursor_row = text.chars().take(cursor - window_start).filter(|&c| c == '\n').count();
+
+        // The distance in rows to the nearest use of each word, and how many uses it has.
+        let mut found: HashMap<String, (usize, usize)> = HashMap::new();
+        for (row, index, word) in words(&text) {
+            if window_start + index != start && proposed(&word) {
+                let (distance, count) = found.entry(word).or_insert((usize::MAX, 0));
+                *distance = (*distance).min(row.abs_diff(cursor_row));
+                *count += 1;
+            }
+        }
+        for (word, count) in others.iter().filter(|(word, _)| proposed(word)) {
+            found.entry(word.clone()).or_insert((usize::MAX, 0)).1 += count;
+        }
+        if found.is_empty() {
+            return None;
+        }
+
+        // Rows about as far away count as equally near: 1, 2 to 3, 4 to 7 and so on.
+        let mut ranked: Vec<(String, (usize, usize))> = found.into_iter().collect();
+        ranked.sort_by_key(|(word, (distance, count))| (usize::BITS - distance.leading_zeros(), Reverse(*count), word.clone()));
+        Some(Completion {
+            prefix,
+            words: ranked.into_iter().map(|(word, _)| word).collect(),
+            scroll: 0,
+            selected: 0,
+        })
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.selected = ((self.selected as isize + offset).max(0) as usize).min(self.words.len() - 1);
+    }
+
+    // What is left to type of the selected word.
+    pub fn remainder(&self) -> &str {
+        &self.words[self.selected][self.prefix.len()..]
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    brackets,
+    gap_buffer::GapBuffer,
+    indentation::{self, IndentStyle},
+    language::{language_for, Language},
+    scrolling::Scroll,
+    selection::Selections,
+    settings,
+};
+
+// An open file, or an untitled one that has not been saved yet, together with
+// its cursors and scroll position.
+pub struct Document {
+    pub buffer: GapBuffer,
+    pub path: Option<PathBuf>,
+    pub has_been_saved: bool,
+    pub selections: Selections,
+    pub scroll: Scroll,
+    pub indent: IndentStyle,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document {
+            buffer: GapBuffer::new(1024),
+            path: None,
+            has_been_saved: false,
+            selections: Selections::new(),
+            scroll: Scroll::default(),
+            indent: IndentStyle::Spaces(settings::tab_width),
+        }
+    }
+
+    pub fn open(path: &Path) -> Option<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Unable to open file: {:?}", e);
+                return None;
+            }
+        };
+        let mut contents = String::new();
+        if let Err(e) = file.read_to_string(&mut contents) {
+            eprintln!("Unable to read file: {:?}", e);
+            return None;
+        }
+
+        let mut document = Document::new();
+        document.buffer.load(&contents);
+        if let Some(indent) = IndentStyle::detect(&contents) {
+            document.indent = indent;
+        }
+        document.path = Some(path.to_path_buf());
+        document.has_been_saved = true;
+        Some(document)
+    }
+
+    // Writes the buffer to `path`, which becomes the document's path.
+    pub fn save(&mut self, path: &Path) -> bool {
+        let mut file = match File::create(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Unable to open file: {:?}", e);
+                return false;
+            }
+        };
+        if let Err(e) = file.write_all(self.buffer.text().as_bytes()) {
+            eprintln!("Could not save to file: {:?}", e);
+            return false;
+        }
+        self.path = Some(path.to_path_buf());
+        self.has_been_saved = true;
+        true
+    }
+
+    pub fn language(&self) -> Option<&'static Language> {
+        self.path.as_deref().and_then(language_for)
+    }
+
+    // The text of one level of indentation.
+    pub fn indent_unit(&self) -> String {
+        self.indent.unit()
+    }
+
+    // Types text at each cursor. Brackets and quotes are paired, and a closing
+    // bracket typed at the start of a line dedents it.
+    pub fn type_text(&mut self, text: &str) {
+        let mut chars = text.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            let dedents = self.language().is_some_and(|language| language.dedent_on.contains(&c));
+            let unit = self.indent_unit();
+            self.selections.edit_with(&mut self.buffer, |buffer, selection| {
+                settings::AUTO_CLOSE_BRACKETS
+                    .then(|| brackets::type_char(buffer, selection, c))
+                    .flatten()
+                    .or_else(|| dedents.then(|| indentation::type_closer(buffer, selection, c, &unit)))
+                    .unwrap_or_else(|| (selection.start(), selection.end(), text.to_string(), 1))
+            });
+        } else {
+            self.selections.insert_text(&mut self.buffer, text);
+        }
+        self.has_been_saved = false;
+    }
+
+    // Breaks the line at each cursor, indenting the new line.
+    pub fn newline(&mut self) {
+        let (language, unit) = (self.language(), self.indent_unit());
+        self.selections.edit_with(&mut self.buffer, |buffer, selection| {
+            indentation::newline(buffer, selection, language, &unit)
+        });
+        self.has_been_saved = false;
+    }
+
+    // Deletes before each cursor, taking an empty bracket pair or a level of
+    // indentation at once.
+    pub fn delete_backward(&mut self) {
+        let indent = self.indent;
+        self.selections.edit_with(&mut self.buffer, |buffer, selection| {
+            brackets::delete_pair(buffer, selection)
+                .filter(|_| settings::AUTO_CLOSE_BRACKETS)
+                .unwrap_or_else(|| indentation::backspace(buffer, selection, indent))
+        });
+        self.has_been_saved = false;
+    }
+
+    pub fn delete_forward(&mut self) {
+        self.selections.delete_forward(&mut self.buffer);
+        self.has_been_saved = false;
+    }
+
+    // Tab indents the lines of a selection spanning several of them.
+    pub fn insert_tab(&mut self) {
+        let indent = self.indent;
+        let buffer = &self.buffer;
+        let multi_line = self.selections.iter().any(|selection| {
+            (selection.start()..selection.end()).any(|index| buffer.get_char(index) == '\n')
+        });
+        if multi_line {
+            indentation::shift_lines(&mut self.buffer, &mut self.selections, indent, false);
+        } else {
+            self.selections.edit_with(&mut self.buffer, |buffer, selection| {
+                indentation::insert_indent(buffer, selection, indent)
+            });
+        }
+        self.has_been_saved = false;
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|os_str| os_str.to_str())
+            .map_or("Untitled".to_string(), |str| str.to_string())
+    }
+
+    // The name with a marker for unsaved changes, as shown in the title and tabs.
+    pub fn title(&self) -> String {
+        format!("{}{}", self.name(), if self.has_been_saved { "" } else { "*" })
+    }
+}
+
+// The open documents in tab order. There is always at least one.
+pub struct Documents {
+    documents: Vec<Document>,
+    active: usize,
+    // Recently closed documents, most recent last, so they can be reopened.
+    closed: Vec<Document>,
+}
+
+impl Documents {
+    pub fn new() -> Self {
+        Documents {
+            documents: vec![Document::new()],
+            active: 0,
+            closed: Vec::new(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Document> {
+        self.documents.iter()
+    }
+
+    pub fn get(&self, index: usize) -> &Document {
+        &self.documents[index]
+    }
+
+    pub fn active(&self) -> &Document {
+        &self.documents[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Document {
+        &mut self.documents[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        self.active = index.min(self.documents.len() - 1);
+    }
+
+    // Switches to the next tab, or the previous one for a negative offset, wrapping around.
+    pub fn cycle(&mut self, offset: isize) {
+        let count = self.documents.len() as isize;
+        self.active = (self.active as isize + offset).rem_euclid(count) as usize;
+    }
+
+    // Opens a document in a new tab after the active one and switches to it.
+    pub fn add(&mut self, document: Document) {
+        self.active += 1;
+        self.documents.insert(self.active, document);
+    }
+
+    // Switches to the tab showing `path`, opening it first if needed.
+    pub fn open(&mut self, path: &Path) -> bool {
+        if let Some(index) = self.documents.iter().position(|document| document.path.as_deref() == Some(path)) {
+            self.active = index;
+            return true;
+        }
+        let document = match Document::open(path) {
+            Some(document) => document,
+            None => return false,
+        };
+        // An untouched untitled tab is replaced rather than kept around.
+        let active = self.active();
+        if active.path.is_none() && active.buffer.length() == 1 && !active.buffer.can_undo() {
+            self.documents[self.active] = document;
+        } else {
+            self.add(document);
+        }
+        true
+    }
+
+    // Follows a file or folder that was renamed, so its open documents save to the new place.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) {
+        for document in &mut self.documents {
+            let renamed = document
+                .path
+                .as_ref()
+                .and_then(|path| path.strip_prefix(from).ok())
+                .map(|rest| if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) });
+            if renamed.is_some() {
+                document.path = renamed;
+            }
+        }
+    }
+
+    pub fn close(&mut self, index: usize) {
+        let document = self.documents.remove(index);
+        self.closed.push(document);
+        if self.closed.len() > settings::CLOSED_DOCUMENTS_LIMIT {
+            self.closed.remove(0);
+        }
+
+        if self.documents.is_empty() {
+            self.documents.push(Document::new());
+        }
+        if index < self.active || self.active >= self.documents.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+
+    // Reopens the most recently closed document. Returns false if there is none.
+    pub fn reopen_closed(&mut self) -> bool {
+        match self.closed.pop() {
+            Some(document) => {
+                self.add(document);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -1 +1,283 @@
+use std::{collections::HashMap, fmt, fs, path::PathBuf};
 
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::{
+    commands::{command_by_name, Command, COMMANDS},
+    settings,
+};
+
+const KEYBINDINGS_FILE: &str = "keybindings";
+
+// The Emacs profile, in the format of the keybindings file. It replaces the
+// default bindings it conflicts with, like Ctrl+X for cut.
+const EMACS_PROFILE: &str = "
+Ctrl+A = cursor.lineStart
+Ctrl+E = cursor.lineEnd
+Ctrl+F = cursor.right
+Ctrl+B = cursor.left
+Ctrl+N = cursor.down
+Ctrl+P = cursor.up
+Alt+F = cursor.wordRight
+Alt+B = cursor.wordLeft
+Ctrl+V = cursor.pageDown
+Alt+V = cursor.pageUp
+Alt+Shift+, = cursor.documentStart
+Alt+Shift+. = cursor.documentEnd
+Ctrl+D = edit.deleteForward
+Ctrl+K = edit.killLine
+Ctrl+W = edit.killRegion
+Alt+W = edit.copyRegion
+Ctrl+Y = edit.yank
+Alt+Y = edit.yankPop
+Ctrl+/ = edit.undo
+Ctrl+Space = selection.setMark
+Ctrl+G = selection.clear
+Ctrl+S = find.open
+Ctrl+R = find.previous
+Alt+X = view.commandPalette
+Ctrl+X Ctrl+S = file.save
+Ctrl+X Ctrl+F = file.open
+Ctrl+X K = file.close
+Ctrl+X U = edit.undo
+Ctrl+X 2 = view.splitStacked
+Ctrl+X 3 = view.splitSideBySide
+Ctrl+X O = view.focusNextPane
+Ctrl+X Ctrl+C = app.quit
+";
+
+// The built-in set of bindings the user's keybindings file is applied on top of.
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeymapProfile {
+    Default,
+    Emacs,
+}
+
+// A key together with the modifiers held down with it, e.g. Ctrl+Shift+P.
+// Either Ctrl, Shift or Alt key counts.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyBinding {
+    pub keycode: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    pub fn from_event(keycode: Keycode, keymod: Mod) -> Self {
+        KeyBinding {
+            keycode,
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        }
+    }
+
+    // Parses e.g. "Ctrl+Shift+P". Key names are SDL's, like "Return" or "PageUp".
+    pub fn parse(text: &str) -> Option<Self> {
+        let (modifiers, key) = match text.rsplit_once('+') {
+            // "Ctrl++" binds the plus key.
+            Some((modifiers, "")) => (modifiers.strip_suffix('+')?, "+"),
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", text),
+        };
+        let mut binding = KeyBinding {
+            keycode: Keycode::from_name(key)?,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => binding.ctrl = true,
+                "shift" => binding.shift = true,
+                "alt" => binding.alt = true,
+                _ => return None,
+            }
+        }
+        Some(binding)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl+"), (self.alt, "Alt+"), (self.shift, "Shift+")] {
+            if held {
+                write!(f, "{}", name)?;
+            }
+        }
+        write!(f, "{}", self.keycode.name())
+    }
+}
+
+// Parses a sequence of keys separated by spaces, e.g. "Ctrl+K Ctrl+C".
+fn parse_sequence(text: &str) -> Option<Vec<KeyBinding>> {
+    let sequence: Option<Vec<KeyBinding>> = text.split_whitespace().map(KeyBinding::parse).collect();
+    sequence.filter(|sequence| !sequence.is_empty())
+}
+
+fn sequence_to_string(sequence: &[KeyBinding]) -> String {
+    sequence.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+// What a key press did.
+pub enum KeyResult {
+    Command(Command),
+    // The key started or continued a chord; the next key decides.
+    Pending,
+    // The key did not complete the chord in progress, which is dropped.
+    ChordCancelled,
+    Unbound,
+}
+
+// Maps keys, or sequences of keys, to commands. The defaults come from the
+// command registry, and the user's keybindings file adds to or overrides them.
+pub struct Keymap {
+    profile: KeymapProfile,
+    bindings: HashMap<Vec<KeyBinding>, Command>,
+    // Keys pressed so far of a chord in progress.
+    pending: Vec<KeyBinding>,
+}
+
+impl Keymap {
+    fn defaults() -> Self {
+        let mut bindings = HashMap::new();
+        for info in COMMANDS {
+            for key in info.default_keys {
+                let sequence = parse_sequence(key).expect("Default keybindings are valid.");
+                bindings.insert(sequence, info.command);
+            }
+        }
+        Keymap {
+            profile: KeymapProfile::Default,
+            bindings,
+            pending: Vec::new(),
+        }
+    }
+
+    // Loads the bindings of `profile` and the user's keybindings file,
+    // reporting lines that are invalid or conflict with another binding.
+    pub fn load(profile: KeymapProfile) -> Self {
+        let mut keymap = Keymap::defaults();
+        if profile == KeymapProfile::Emacs {
+            for problem in keymap.apply(EMACS_PROFILE) {
+                eprintln!("Emacs profile: {}", problem);
+            }
+            keymap.profile = profile;
+        }
+        let Some(path) = keybindings_path() else {
+            return keymap;
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return keymap;
+        };
+        for problem in keymap.apply(&contents) {
+            eprintln!("{}: {}", path.display(), problem);
+        }
+        keymap
+    }
+
+    // Applies the lines of a keybindings file, e.g. `Ctrl+K Ctrl+C = edit.copy`.
+    // An empty command removes the binding, `#` starts a comment. Returns a
+    // description of each line that was skipped.
+    fn apply(&mut self, contents: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        // The sequences bound by the file so far, and on which line.
+        let mut user_bindings: Vec<(Vec<KeyBinding>, usize)> = Vec::new();
+
+        for (number, line) in contents.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((keys, name)) = line.rsplit_once('=') else {
+                problems.push(format!("line {}: expected `keys = command`", number));
+                continue;
+            };
+            let Some(sequence) = parse_sequence(keys) else {
+                problems.push(format!("line {}: unknown key in `{}`", number, keys.trim()));
+                continue;
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                self.bindings.remove(&sequence);
+                continue;
+            }
+            let Some(command) = command_by_name(name) else {
+                problems.push(format!("line {}: unknown command `{}`", number, name));
+                continue;
+            };
+
+            // A sequence that is bound twice, or that starts with another bound
+            // sequence, could never run both commands.
+            let conflict = user_bindings.iter().find(|(other, _)| {
+                other.starts_with(&sequence) || sequence.starts_with(other)
+            });
+            if let Some((other, other_number)) = conflict {
+                problems.push(format!(
+                    "line {}: {} conflicts with {} on line {}",
+                    number,
+                    sequence_to_string(&sequence),
+                    sequence_to_string(other),
+                    other_number
+                ));
+                continue;
+            }
+
+            // Default bindings in the way give way to the user's.
+            self.bindings.retain(|other, _| !other.starts_with(&sequence) && !sequence.starts_with(other));
+            self.bindings.insert(sequence.clone(), command);
+            user_bindings.push((sequence, number));
+        }
+        problems
+    }
+
+    pub fn profile(&self) -> KeymapProfile {
+        self.profile
+    }
+
+    // Translates a key press into a command, keeping track of chords. Keys
+    // that are only modifiers are ignored so they can be held for the next key.
+    pub fn handle(&mut self, keycode: Keycode, keymod: Mod) -> KeyResult {
+        if matches!(
+            keycode,
+            Keycode::LCtrl | Keycode::RCtrl | Keycode::LShift | Keycode::RShift | Keycode::LAlt | Keycode::RAlt
+        ) {
+            return KeyResult::Unbound;
+        }
+
+        let chord_in_progress = !self.pending.is_empty();
+        self.pending.push(KeyBinding::from_event(keycode, keymod));
+        if let Some(&command) = self.bindings.get(&self.pending) {
+            self.pending.clear();
+            return KeyResult::Command(command);
+        }
+        if self.bindings.keys().any(|sequence| sequence.starts_with(&self.pending)) {
+            return KeyResult::Pending;
+        }
+        self.pending.clear();
+        if chord_in_progress {
+            KeyResult::ChordCancelled
+        } else {
+            KeyResult::Unbound
+        }
+    }
+
+    // The keys of a chord in progress, e.g. for showing them in the status bar.
+    pub fn pending(&self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| sequence_to_string(&self.pending))
+    }
+
+    // Returns the shortest key sequence bound to a command, as the command palette shows it.
+    pub fn shortcut(&self, command: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, &bound)| bound == command)
+            .map(|(sequence, _)| sequence)
+            .map(|sequence| sequence_to_string(sequence))
+            .min_by_key(|shortcut| (shortcut.split(' ').count(), shortcut.len(), shortcut.clone()))
+    }
+}
+
+fn keybindings_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(KEYBINDINGS_FILE))
+}
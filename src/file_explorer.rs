@@ -0,0 +1,289 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+    expanded: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExplorerAction {
+    NewFile,
+    NewFolder,
+    Rename,
+}
+
+// What a confirmed prompt changed on disk.
+pub enum ExplorerChange {
+    Created(PathBuf),
+    Renamed(PathBuf, PathBuf),
+}
+
+// Tree of the files under a directory, as shown in the sidebar. Only the
+// visible entries are kept, in display order, and a folder's contents are
+// read when it is first expanded.
+pub struct FileExplorer {
+    root: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    pub scroll: usize,
+    // The action waiting for a name to be typed, and the name so far.
+    pub prompt: Option<(ExplorerAction, String)>,
+    error: Option<String>,
+}
+
+impl FileExplorer {
+    pub fn new(root: &Path) -> Self {
+        FileExplorer {
+            root: root.to_path_buf(),
+            entries: read_entries(root, 0),
+            selected: 0,
+            scroll: 0,
+            prompt: None,
+            error: None,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_path(&self) -> Option<&Path> {
+        self.entries.get(self.selected).map(|entry| entry.path.as_path())
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.select((self.selected as isize + offset).max(0) as usize);
+    }
+
+    // Expands or collapses the folder at `index`. Returns the path if the
+    // entry is a file instead, so the caller can open it.
+    pub fn activate(&mut self, index: usize) -> Option<PathBuf> {
+        let entry = self.entries.get(index)?;
+        if !entry.is_dir {
+            return Some(entry.path.clone());
+        }
+        if entry.expanded {
+            self.collapse(index);
+        } else {
+            self.expand(index);
+        }
+        None
+    }
+
+    // Right expands the selected folder, or moves into it if it is already expanded.
+    pub fn expand_selected(&mut self) {
+        match self.entries.get(self.selected) {
+            Some(entry) if entry.is_dir && !entry.expanded => self.expand(self.selected),
+            Some(entry) if entry.is_dir => self.select_by(1),
+            _ => {}
+        }
+    }
+
+    // Left collapses the selected folder, or moves to the folder containing the selection.
+    pub fn collapse_selected(&mut self) {
+        let entry = match self.entries.get(self.selected) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if entry.expanded {
+            self.collapse(self.selected);
+        } else if let Some(parent) = self.entries[..self.selected]
+            .iter()
+            .rposition(|parent| parent.depth < entry.depth)
+        {
+            self.selected = parent;
+        }
+    }
+
+    fn expand(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        if !entry.is_dir || entry.expanded {
+            return;
+        }
+        entry.expanded = true;
+        let children = read_entries(&entry.path, entry.depth + 1);
+        self.entries.splice(index + 1..index + 1, children);
+    }
+
+    fn collapse(&mut self, index: usize) {
+        let depth = self.entries[index].depth;
+        self.entries[index].expanded = false;
+        let end = self.entries[index + 1..]
+            .iter()
+            .position(|entry| entry.depth <= depth)
+            .map_or(self.entries.len(), |offset| index + 1 + offset);
+        self.entries.drain(index + 1..end);
+        if self.selected > index {
+            self.selected = if self.selected < end { index } else { self.selected - (end - index - 1) };
+        }
+    }
+
+    // Reads the tree from disk again, keeping the expanded folders open and
+    // selecting `selected` if it is still there.
+    fn reload(&mut self, expanded: &[PathBuf], selected: Option<&Path>) {
+        self.entries = read_entries(&self.root, 0);
+        let mut index = 0;
+        while index < self.entries.len() {
+            if expanded.contains(&self.entries[index].path) {
+                self.expand(index);
+            }
+            index += 1;
+        }
+        let selected = selected.and_then(|path| self.entries.iter().position(|entry| entry.path == path));
+        self.select(selected.unwrap_or(self.selected));
+    }
+
+    pub fn refresh(&mut self) {
+        let selected = self.selected_path().map(Path::to_path_buf);
+        self.reload(&self.expanded_paths(), selected.as_deref());
+    }
+
+    fn expanded_paths(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.expanded)
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    // Asks for a name in the sidebar. Renaming starts from the current name.
+    pub fn start_prompt(&mut self, action: ExplorerAction) {
+        let name = match action {
+            ExplorerAction::Rename => match self.selected_path().and_then(|path| path.file_name()) {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return,
+            },
+            _ => String::new(),
+        };
+        self.error = None;
+        self.prompt = Some((action, name));
+    }
+
+    // Carries out the prompt with the typed name. The prompt stays open if
+    // that fails, showing why.
+    pub fn confirm_prompt(&mut self) -> Option<ExplorerChange> {
+        let (action, name) = self.prompt.clone()?;
+        if name.is_empty() || name.contains(['/', '\\']) {
+            self.error = Some("Invalid name".to_string());
+            return None;
+        }
+
+        // New entries go into the selected folder, or next to the selected file.
+        let selected = self.entries.get(self.selected);
+        let (parent, old_path) = match (action, selected) {
+            (ExplorerAction::Rename, Some(entry)) => {
+                (entry.path.parent().unwrap_or(&self.root).to_path_buf(), Some(entry.path.clone()))
+            }
+            (ExplorerAction::Rename, None) => return None,
+            (_, Some(entry)) if entry.is_dir => (entry.path.clone(), None),
+            (_, Some(entry)) => (entry.path.parent().unwrap_or(&self.root).to_path_buf(), None),
+            (_, None) => (self.root.clone(), None),
+        };
+        let path = parent.join(&name);
+        if old_path.as_ref() != Some(&path) && path.exists() {
+            self.error = Some(format!("{} already exists", name));
+            return None;
+        }
+
+        let result = match (action, &old_path) {
+            (ExplorerAction::NewFile, _) => fs::write(&path, ""),
+            (ExplorerAction::NewFolder, _) => fs::create_dir(&path),
+            (ExplorerAction::Rename, Some(old_path)) => fs::rename(old_path, &path),
+            (ExplorerAction::Rename, None) => return None,
+        };
+        if let Err(e) = result {
+            self.error = Some(format!("Failed: {}", e));
+            return None;
+        }
+
+        self.prompt = None;
+        self.error = None;
+        let mut expanded = self.expanded_paths();
+        expanded.push(parent);
+        self.reload(&expanded, Some(&path));
+        Some(match old_path {
+            Some(old_path) => ExplorerChange::Renamed(old_path, path),
+            None => ExplorerChange::Created(path),
+        })
+    }
+
+    // Deletes the selected file, or the selected folder with everything in it.
+    pub fn delete_selected(&mut self) -> bool {
+        let entry = match self.entries.get(self.selected) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let result = if entry.is_dir {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+        if let Err(e) = result {
+            self.error = Some(format!("Failed: {}", e));
+            return false;
+        }
+        self.error = None;
+        self.refresh();
+        true
+    }
+
+    pub fn status(&self) -> String {
+        self.error.clone().unwrap_or_default()
+    }
+
+    // Returns the text of each visible entry, indented by its depth.
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let marker = match (entry.is_dir, entry.expanded) {
+                    (true, true) => "- ",
+                    (true, false) => "+ ",
+                    (false, _) => "  ",
+                };
+                let name = entry.path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+                format!("{}{}{}", "  ".repeat(entry.depth), marker, name)
+            })
+            .collect()
+    }
+}
+
+// Returns the contents of a folder, folders first, each group sorted by name.
+fn read_entries(dir: &Path, depth: usize) -> Vec<Entry> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            eprintln!("Unable to read directory: {:?}", e);
+            return Vec::new();
+        }
+    };
+    let mut entries: Vec<Entry> = read_dir
+        .flatten()
+        .filter(|entry| entry.file_name() != ".git")
+        .map(|entry| Entry {
+            is_dir: entry.file_type().is_ok_and(|file_type| file_type.is_dir()),
+            path: entry.path(),
+            depth,
+            expanded: false,
+        })
+        .collect();
+    entries.sort_by_key(|entry| (!entry.is_dir, entry.path.file_name().map(|name| name.to_ascii_lowercase())));
+    entries
+}
@@ -0,0 +1,209 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use ignore::WalkBuilder;
+
+use crate::{fuzzy::fuzzy_score, settings};
+
+// The Ctrl+P file finder. The files under the working directory are indexed
+// on a background thread, skipping whatever `.gitignore` files exclude, and
+// ranked by how well their path fuzzy-matches the query.
+pub struct FileFinder {
+    pub query: String,
+    pub scroll: usize,
+    root: PathBuf,
+    // Paths relative to the root, in the order they were found.
+    files: Vec<PathBuf>,
+    // Indices into `files`, best match first.
+    results: Vec<usize>,
+    selected: usize,
+    ranked_query: Option<String>,
+    // The selected file and the first lines of it.
+    preview: Option<(usize, String)>,
+    receiver: Option<Receiver<PathBuf>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FileFinder {
+    pub fn new() -> Self {
+        FileFinder {
+            query: String::new(),
+            scroll: 0,
+            root: PathBuf::new(),
+            files: Vec::new(),
+            results: Vec::new(),
+            selected: 0,
+            ranked_query: None,
+            preview: None,
+            receiver: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Clears the query and indexes `root` again, so files created since the
+    // last time show up.
+    pub fn start(&mut self, root: &Path) {
+        self.cancel();
+        self.query.clear();
+        self.scroll = 0;
+        self.root = root.to_path_buf();
+        self.files.clear();
+        self.results.clear();
+        self.selected = 0;
+        self.ranked_query = None;
+        self.preview = None;
+
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.receiver = Some(receiver);
+        self.cancelled = cancelled.clone();
+
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            for entry in WalkBuilder::new(&root).build().flatten() {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                    continue;
+                }
+                let path = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                if sender.send(path.to_path_buf()).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.receiver = None;
+    }
+
+    // Picks up newly indexed files and ranks the files again if anything changed.
+    pub fn update(&mut self) {
+        let mut received = false;
+        if let Some(receiver) = &self.receiver {
+            loop {
+                match receiver.try_recv() {
+                    Ok(path) => {
+                        self.files.push(path);
+                        received = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.receiver = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let query_changed = self.ranked_query.as_ref() != Some(&self.query);
+        if !received && !query_changed {
+            return;
+        }
+        let mut scored: Vec<(i32, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, path)| Some((path_score(&self.query, path)?, index)))
+            .collect();
+        scored.sort_by_key(|&(score, index)| (-score, self.files[index].as_os_str().len(), index));
+        self.results = scored.into_iter().map(|(_, index)| index).collect();
+        self.ranked_query = Some(self.query.clone());
+        if query_changed {
+            self.selected = 0;
+            self.scroll = 0;
+        }
+        self.select(self.selected);
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.results.len().saturating_sub(1));
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.select((self.selected as isize + offset).max(0) as usize);
+    }
+
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        let index = *self.results.get(self.selected)?;
+        Some(self.root.join(&self.files[index]))
+    }
+
+    // Returns the first lines of the selected file, read when it is first selected.
+    pub fn preview(&mut self) -> &str {
+        let index = match self.results.get(self.selected) {
+            Some(&index) => index,
+            None => return "",
+        };
+        if self.preview.as_ref().map(|(previewed, _)| *previewed) != Some(index) {
+            let contents = read_start(&self.root.join(&self.files[index])).unwrap_or_default();
+            let lines: Vec<&str> = contents.lines().take(settings::FILE_FINDER_PREVIEW_LINES as usize).collect();
+            self.preview = Some((index, lines.join("\n")));
+        }
+        self.preview.as_ref().map_or("", |(_, preview)| preview)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .map(|&index| self.files[index].display().to_string())
+            .collect()
+    }
+
+    // Returns e.g. "12 of 340 files", marked while indexing is still running.
+    pub fn status(&self) -> String {
+        let status = format!("{} of {} files", self.results.len(), self.files.len());
+        if self.receiver.is_some() {
+            format!("{}...", status)
+        } else {
+            status
+        }
+    }
+}
+
+// Reads the start of a file for its preview, so a large file does not hold up
+// the editor. Files that are not text get no preview.
+fn read_start(path: &Path) -> Option<String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(settings::FILE_FINDER_PREVIEW_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    match String::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        // Only a character cut off at the end of what was read is allowed.
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+// Scores a path for the query, or returns None if it does not match. A match
+// within the file name alone beats one spread over the whole path.
+fn path_score(query: &str, path: &Path) -> Option<i32> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let file_name = path.rsplit('/').next().unwrap_or(&path);
+    let in_file_name = fuzzy_score(query, file_name).map(|score| score + 10);
+    fuzzy_score(query, &path).max(in_file_name)
+}
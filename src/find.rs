@@ -0,0 +1,341 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    constants::EOF_CHAR,
+    gap_buffer::{Change, GapBuffer},
+    motions::is_word_char,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FindField {
+    Query,
+    Replacement,
+}
+
+// State of the find bar: the query, its options and the matches it found in
+// the buffer. Matches are `start..end` character ranges in text order.
+pub struct Find {
+    pub query: String,
+    pub replacement: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    // Whether the replacement field is shown, and which field has the keyboard.
+    pub replacing: bool,
+    pub focus: FindField,
+    // Range of the text that matches are limited to, e.g. the selection. It
+    // moves along with edits, and already has the first `scope_changes_seen`
+    // of the buffer's pending changes applied.
+    scope: Option<(usize, usize)>,
+    scope_changes_seen: usize,
+    pattern: Option<Regex>,
+    error: Option<String>,
+    matches: Vec<(usize, usize)>,
+    current: Option<usize>,
+}
+
+impl Find {
+    pub fn new() -> Self {
+        Find {
+            query: String::new(),
+            replacement: String::new(),
+            case_sensitive: false,
+            whole_word: false,
+            regex: false,
+            replacing: false,
+            focus: FindField::Query,
+            scope: None,
+            scope_changes_seen: 0,
+            pattern: None,
+            error: None,
+            matches: Vec::new(),
+            current: None,
+        }
+    }
+
+    pub fn matches(&self) -> &[(usize, usize)] {
+        &self.matches
+    }
+
+    pub fn current(&self) -> Option<(usize, usize)> {
+        self.current.map(|current| self.matches[current])
+    }
+
+    pub fn scope(&self) -> Option<(usize, usize)> {
+        self.scope
+    }
+
+    pub fn set_scope(&mut self, scope: Option<(usize, usize)>, buffer: &GapBuffer) {
+        self.scope = scope.filter(|(start, end)| start < end);
+        self.scope_changes_seen = buffer.changes().len();
+    }
+
+    // Moves the scope along with the changes taken from the buffer.
+    pub fn follow_changes(&mut self, changes: &[Change]) {
+        self.map_scope(&changes[self.scope_changes_seen.min(changes.len())..]);
+        self.scope_changes_seen = 0;
+    }
+
+    // Moves the scope along with the buffer's pending changes it has not seen yet.
+    fn follow_pending_changes(&mut self, buffer: &GapBuffer) {
+        let changes = buffer.changes();
+        self.map_scope(&changes[self.scope_changes_seen.min(changes.len())..]);
+        self.scope_changes_seen = changes.len();
+    }
+
+    // Text inserted at the start or end of the scope goes inside it.
+    fn map_scope(&mut self, changes: &[Change]) {
+        if let Some((mut start, mut end)) = self.scope {
+            for change in changes {
+                start = change.map(start, true);
+                end = change.map(end, false);
+            }
+            self.scope = Some((start, end)).filter(|(start, end)| start < end);
+        }
+    }
+
+    // Returns the text field that has the keyboard.
+    pub fn focused_field(&mut self) -> &mut String {
+        match self.focus {
+            FindField::Query => &mut self.query,
+            FindField::Replacement => &mut self.replacement,
+        }
+    }
+
+    // Returns the row of each match, e.g. for marking them on the scroll bar.
+    pub fn match_rows(&self, buffer: &GapBuffer) -> Vec<usize> {
+        let text = buffer.to_string();
+        let mut chars = text.chars();
+        let mut row = 0;
+        let mut index = 0;
+        self.matches
+            .iter()
+            .map(|&(start, _)| {
+                while index < start {
+                    if chars.next() == Some('\n') {
+                        row += 1;
+                    }
+                    index += 1;
+                }
+                row
+            })
+            .collect()
+    }
+
+    // Searches the whole buffer again, keeping the current match if it still exists.
+    pub fn search(&mut self, buffer: &GapBuffer) {
+        self.follow_pending_changes(buffer);
+        let previous = self.current();
+        self.matches.clear();
+        self.current = None;
+        self.pattern = None;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        // Plain queries are escaped so both kinds of search share one matcher.
+        let source = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        let pattern = match RegexBuilder::new(&source)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+        {
+            Ok(pattern) => pattern,
+            Err(_) => {
+                self.error = Some("Invalid regex".to_string());
+                return;
+            }
+        };
+
+        let text = buffer.to_string();
+        let text = text.strip_suffix(EOF_CHAR).unwrap_or(&text);
+        let chars: Vec<char> = text.chars().collect();
+        let (scope_start, scope_end) = self.scope.unwrap_or((0, chars.len()));
+
+        // Regex offsets are in bytes, the buffer works in characters.
+        let byte_offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).collect();
+        let char_index = |byte: usize| byte_offsets.partition_point(|&offset| offset < byte);
+
+        // A match that is not a whole word may hide one that starts inside it,
+        // so the search goes on from the next character instead of past the match.
+        let mut from = byte_offsets.get(scope_start).copied().unwrap_or(text.len());
+        while let Some(found) = pattern.find_at(text, from) {
+            let start = char_index(found.start());
+            let end = char_index(found.end());
+            let is_whole_word = (start == 0 || !is_word_char(chars[start - 1]))
+                && (end == chars.len() || !is_word_char(chars[end]));
+            if start == end || (self.whole_word && !is_whole_word) {
+                match byte_offsets.get(start + 1) {
+                    Some(&next) => from = next,
+                    None => break,
+                }
+                continue;
+            }
+            if start >= scope_start && end <= scope_end {
+                self.matches.push((start, end));
+            }
+            from = found.end();
+        }
+
+        self.pattern = Some(pattern);
+        self.current = previous.and_then(|previous| self.matches.iter().position(|&m| m == previous));
+    }
+
+    // Makes the first match at or after `index` current, wrapping around to the
+    // first match of the text.
+    pub fn select_from(&mut self, index: usize) -> Option<(usize, usize)> {
+        if self.matches.is_empty() {
+            self.current = None;
+        } else {
+            self.current = Some(self.matches.iter().position(|&(start, _)| start >= index).unwrap_or(0));
+        }
+        self.current()
+    }
+
+    pub fn next(&mut self, cursor: usize) -> Option<(usize, usize)> {
+        match self.current {
+            Some(current) if !self.matches.is_empty() => {
+                self.current = Some((current + 1) % self.matches.len());
+                self.current()
+            }
+            _ => self.select_from(cursor),
+        }
+    }
+
+    pub fn previous(&mut self, cursor: usize) -> Option<(usize, usize)> {
+        match self.current {
+            Some(current) if !self.matches.is_empty() => {
+                self.current = Some((current + self.matches.len() - 1) % self.matches.len());
+                self.current()
+            }
+            _ => {
+                let previous = self.matches.iter().rposition(|&(start, _)| start < cursor);
+                self.current = previous.or_else(|| self.matches.len().checked_sub(1));
+                self.current()
+            }
+        }
+    }
+
+    // Returns the text a match is replaced with. In regex mode `$1` or `${name}`
+    // in the replacement refer to the match's capture groups.
+    fn replacement_for(&self, text: &str, (start, _): (usize, usize)) -> String {
+        let pattern = match &self.pattern {
+            Some(pattern) if self.regex => pattern,
+            _ => return self.replacement.clone(),
+        };
+        let byte_start = text.char_indices().nth(start).map_or(text.len(), |(offset, _)| offset);
+
+        let mut replaced = String::new();
+        if let Some(captures) = pattern
+            .captures_at(text, byte_start)
+            .filter(|captures| captures.get(0).is_some_and(|m| m.start() == byte_start))
+        {
+            captures.expand(&self.replacement, &mut replaced);
+        }
+        replaced
+    }
+
+    // Replaces the current match and makes the next one current. Returns the
+    // next match, if any is left.
+    pub fn replace_current(&mut self, buffer: &mut GapBuffer) -> Option<(usize, usize)> {
+        let (start, end) = self.current()?;
+        // Without the end-of-file marker, like the text searched, so `$` matches the same.
+        let replaced = self.replacement_for(&buffer.text(), (start, end));
+        let inserted = replaced.chars().count();
+
+        buffer.begin_undo_group();
+        buffer.delete_range(start, end);
+        buffer.insert_str(start, &replaced);
+        buffer.end_undo_group();
+
+        self.current = None;
+        self.search(buffer);
+        self.select_from(start + inserted)
+    }
+
+    // Replaces every match as a single undo step and returns how many were replaced.
+    pub fn replace_all(&mut self, buffer: &mut GapBuffer) -> usize {
+        let text = buffer.text();
+        let replacements: Vec<((usize, usize), String)> = self
+            .matches
+            .iter()
+            .map(|&range| (range, self.replacement_for(&text, range)))
+            .collect();
+
+        buffer.begin_undo_group();
+        // Replace from the end so earlier matches keep their positions.
+        for ((start, end), replaced) in replacements.iter().rev() {
+            buffer.delete_range(*start, *end);
+            buffer.insert_str(*start, replaced);
+        }
+        buffer.end_undo_group();
+
+        self.current = None;
+        self.search(buffer);
+        replacements.len()
+    }
+
+    // Returns e.g. "3 of 17", or "No results" if a query finds nothing.
+    pub fn status(&self) -> String {
+        if let Some(error) = &self.error {
+            error.clone()
+        } else if self.query.is_empty() {
+            String::new()
+        } else if self.matches.is_empty() {
+            "No results".to_string()
+        } else {
+            match self.current {
+                Some(current) => format!("{} of {}", current + 1, self.matches.len()),
+                None => format!("{} matches", self.matches.len()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace_all(text: &str, query: &str, replacement: &str) -> String {
+        let mut buffer = GapBuffer::new(64);
+        buffer.load(text);
+        let mut find = Find::new();
+        find.regex = true;
+        find.query = query.to_string();
+        find.replacement = replacement.to_string();
+        find.search(&buffer);
+        find.replace_all(&mut buffer);
+        buffer.text()
+    }
+
+    #[test]
+    fn replaces_matches_anchored_at_the_end() {
+        assert_eq!(replace_all("foo", "(o)$", "0"), "fo0");
+        assert_eq!(replace_all("a b word", r"\w+\z", "end"), "a b end");
+    }
+
+    #[test]
+    fn expands_capture_groups() {
+        assert_eq!(replace_all("user@host", r"(\w+)@(\w+)", "$2 at $1"), "host at user");
+        assert_eq!(replace_all("a=1\nb=2", r"(?P<key>\w)=(\d)", "${key}: $2"), "a: 1\nb: 2");
+    }
+
+    #[test]
+    fn replaces_the_current_match_at_the_end() {
+        let mut buffer = GapBuffer::new(64);
+        buffer.load("one two");
+        let mut find = Find::new();
+        find.regex = true;
+        find.query = r"(\w+)$".to_string();
+        find.replacement = "[$1]".to_string();
+        find.search(&buffer);
+        find.select_from(0);
+        find.replace_current(&mut buffer);
+        assert_eq!(buffer.text(), "one [two]");
+    }
+}
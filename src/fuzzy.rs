@@ -0,0 +1,30 @@
+// Scores how well the characters of `query` appear in order in `candidate`,
+// ignoring case. Characters at the start of a path segment or word, and runs
+// of consecutive characters, score higher. Shorter candidates win ties.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let index = (position..candidate.len()).find(|&index| {
+            candidate[index].to_lowercase().eq(query_char.to_lowercase())
+        })?;
+        score += 1;
+        if index > 0 && previous == Some(index - 1) {
+            score += 5;
+        }
+        let before = if index > 0 { Some(candidate[index - 1]) } else { None };
+        match before {
+            None | Some('/') => score += 8,
+            Some('_' | '-' | '.' | ' ') => score += 6,
+            Some(before) if before.is_lowercase() && candidate[index].is_uppercase() => score += 6,
+            _ => {}
+        }
+        previous = Some(index);
+        position = index + 1;
+    }
+
+    Some(score - candidate.len() as i32 / 8)
+}
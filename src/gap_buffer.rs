@@ -1,10 +1,74 @@
 use crate::constants::EOF_CHAR;
 
+// A single change to the text: `removed` was replaced by `inserted` at `position`.
+struct Edit {
+    position: usize,
+    removed: String,
+    inserted: String,
+}
+
+impl Edit {
+    // Extends this edit with `next` if it continues typing or deleting on the same line.
+    fn merge(&mut self, next: &Edit) -> bool {
+        let typing = self.removed.is_empty()
+            && next.removed.is_empty()
+            && self.position + self.inserted.chars().count() == next.position
+            && !next.inserted.contains('\n');
+        let deleting = self.inserted.is_empty()
+            && next.inserted.is_empty()
+            && next.position + next.removed.chars().count() == self.position
+            && !next.removed.contains('\n');
+
+        if typing {
+            self.inserted.push_str(&next.inserted);
+        } else if deleting {
+            self.removed.insert_str(0, &next.removed);
+            self.position = next.position;
+        }
+        typing || deleting
+    }
+}
+
+// A change to the text as character counts: `removed` characters at `position`
+// were replaced by `inserted` ones. Positions held outside the buffer, like the
+// find scope, follow the text through these.
+#[derive(Clone, Copy)]
+pub struct Change {
+    pub position: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+impl Change {
+    // Where `index` is after the change. An index right where text was inserted
+    // stays before it if `stays_before`, and moves past it otherwise.
+    pub fn map(&self, index: usize, stays_before: bool) -> usize {
+        if index < self.position || (index == self.position && stays_before) {
+            index
+        } else if index < self.position + self.removed {
+            // Inside the removed text.
+            self.position + if stays_before { 0 } else { self.inserted }
+        } else {
+            index + self.inserted - self.removed
+        }
+    }
+}
+
 pub struct GapBuffer {
     buffer: Vec<char>,
     gap_start: usize,
     gap_end: usize,
     cursor: usize,
+    // Each entry is one undo step, which may hold several edits, e.g. one per cursor.
+    undo_stack: Vec<Vec<Edit>>,
+    redo_stack: Vec<Vec<Edit>>,
+    undo_group_depth: usize,
+    // Whether the next edit may be merged into the last undo step.
+    coalesce_edits: bool,
+    // Changes since `take_changes` was last called.
+    changes: Vec<Change>,
+    // Goes up with every change to the text.
+    version: usize,
 }
 
 impl GapBuffer {
@@ -17,10 +81,16 @@ impl GapBuffer {
             gap_start: 0,
             gap_end: capacity - 1,
             cursor: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            undo_group_depth: 0,
+            coalesce_edits: false,
+            changes: Vec::new(),
+            version: 0,
         }
     }
 
-    pub fn insert(&mut self, ch: char) {
+    fn insert(&mut self, ch: char) {
         if self.gap_start == self.gap_end {
             self.resize();
         }
@@ -30,7 +100,7 @@ impl GapBuffer {
         self.cursor += 1;
     }
 
-    pub fn remove(&mut self) -> Option<char> {
+    fn remove(&mut self) -> Option<char> {
         if self.cursor == 0 {
             return None;
         } else {
@@ -88,6 +158,10 @@ impl GapBuffer {
     }
 
     pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.changes.clear();
+        self.version += 1;
         self.gap_start = 0;
         self.gap_end = self.buffer.len() - 1;
         self.cursor = 0;
@@ -99,6 +173,187 @@ impl GapBuffer {
         self.buffer[buffer_len - 1] = EOF_CHAR;
     }
 
+    // Replaces the whole text, e.g. with the contents of a file. This cannot be undone.
+    pub fn load(&mut self, text: &str) {
+        self.clear();
+        for c in text.chars() {
+            self.insert(c);
+        }
+        self.move_cursor(0);
+    }
+
+    pub fn insert_str(&mut self, index: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.move_cursor(index);
+        for c in text.chars() {
+            self.insert(c);
+        }
+        self.record_change(Change { position: index, removed: 0, inserted: text.chars().count() });
+        self.record(Edit {
+            position: index,
+            removed: String::new(),
+            inserted: text.to_string(),
+        });
+    }
+
+    // Removes the characters in `start..end` and returns them.
+    pub fn delete_range(&mut self, start: usize, end: usize) -> String {
+        if start >= end {
+            return String::new();
+        }
+        self.move_cursor(end);
+        let mut removed: Vec<char> = (start..end).filter_map(|_| self.remove()).collect();
+        removed.reverse();
+        let removed: String = removed.into_iter().collect();
+        self.record_change(Change { position: start, removed: removed.chars().count(), inserted: 0 });
+
+        self.record(Edit {
+            position: start,
+            removed: removed.clone(),
+            inserted: String::new(),
+        });
+        removed
+    }
+
+    // Edits made until the matching `end_undo_group` are undone as a single step.
+    pub fn begin_undo_group(&mut self) {
+        if self.undo_group_depth == 0 {
+            self.redo_stack.clear();
+            self.undo_stack.push(Vec::new());
+        }
+        self.undo_group_depth += 1;
+    }
+
+    pub fn end_undo_group(&mut self) {
+        self.undo_group_depth -= 1;
+        if self.undo_group_depth == 0 {
+            let group = self.undo_stack.pop().unwrap();
+            if !group.is_empty() {
+                self.push_undo_step(group);
+            }
+        }
+    }
+
+    // Marks the current point in the undo history. Edits after it are not
+    // merged into earlier undo steps.
+    pub fn undo_checkpoint(&mut self) -> usize {
+        self.coalesce_edits = false;
+        self.undo_stack.len()
+    }
+
+    // Joins the undo steps recorded since `checkpoint` into a single step, for
+    // changes made across several events, like a Vim change and the text typed after it.
+    pub fn join_undo_steps(&mut self, checkpoint: usize) {
+        if self.undo_group_depth > 0 || self.undo_stack.len() <= checkpoint + 1 {
+            return;
+        }
+        let joined: Vec<Edit> = self.undo_stack.drain(checkpoint..).flatten().collect();
+        self.undo_stack.push(joined);
+        self.coalesce_edits = false;
+    }
+
+    fn record(&mut self, edit: Edit) {
+        if self.undo_group_depth > 0 {
+            self.undo_stack.last_mut().unwrap().push(edit);
+            return;
+        }
+        self.redo_stack.clear();
+        self.push_undo_step(vec![edit]);
+    }
+
+    // Consecutive single edits that continue each other, like typing a word, are
+    // merged into one undo step.
+    fn push_undo_step(&mut self, group: Vec<Edit>) {
+        let last_group = self.undo_stack.last_mut().filter(|_| self.coalesce_edits);
+        self.coalesce_edits = group.len() == 1;
+        if let (Some([last]), [edit]) = (last_group.map(|group| group.as_mut_slice()), group.as_slice()) {
+            if last.merge(edit) {
+                return;
+            }
+        }
+        self.undo_stack.push(group);
+    }
+
+    // Replaces the text at `position` without recording the change.
+    fn apply(&mut self, position: usize, remove_count: usize, text: &str) {
+        self.move_cursor(position + remove_count);
+        for _ in 0..remove_count {
+            self.remove();
+        }
+        for c in text.chars() {
+            self.insert(c);
+        }
+        self.record_change(Change { position, removed: remove_count, inserted: text.chars().count() });
+    }
+
+    // Reverts the last undo step and returns where the cursors should be placed.
+    pub fn undo(&mut self) -> Option<Vec<usize>> {
+        let group = self.undo_stack.pop()?;
+        let mut cursors: Vec<usize> = Vec::new();
+
+        for edit in group.iter().rev() {
+            let removed_count = edit.removed.chars().count();
+            let inserted_count = edit.inserted.chars().count();
+            self.apply(edit.position, inserted_count, &edit.removed);
+
+            for cursor in cursors.iter_mut().filter(|cursor| **cursor > edit.position) {
+                *cursor = (*cursor + removed_count).saturating_sub(inserted_count);
+            }
+            cursors.push(edit.position + removed_count);
+        }
+
+        self.redo_stack.push(group);
+        self.coalesce_edits = false;
+        Some(cursors)
+    }
+
+    // Reapplies the last undone step and returns where the cursors should be placed.
+    pub fn redo(&mut self) -> Option<Vec<usize>> {
+        let group = self.redo_stack.pop()?;
+        let mut cursors: Vec<usize> = Vec::new();
+
+        for edit in group.iter() {
+            let removed_count = edit.removed.chars().count();
+            let inserted_count = edit.inserted.chars().count();
+            self.apply(edit.position, removed_count, &edit.inserted);
+
+            for cursor in cursors.iter_mut().filter(|cursor| **cursor > edit.position) {
+                *cursor = (*cursor + inserted_count).saturating_sub(removed_count);
+            }
+            cursors.push(edit.position + inserted_count);
+        }
+
+        self.undo_stack.push(group);
+        self.coalesce_edits = false;
+        Some(cursors)
+    }
+
+    fn record_change(&mut self, change: Change) {
+        self.changes.push(change);
+        self.version += 1;
+    }
+
+    // A number that is different after any change to the text, e.g. for
+    // telling whether something worked out from the text is still current.
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    // The changes not taken yet, oldest first.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
     pub fn length(&self) -> usize {
         self.buffer.len() - (self.gap_end - self.gap_start)
     }
@@ -108,11 +363,15 @@ impl GapBuffer {
     }
 
     pub fn get_cursor_position(&self) -> (usize, usize) {
+        self.get_position_of_index(self.cursor)
+    }
+
+    pub fn get_position_of_index(&self, index: usize) -> (usize, usize) {
         let mut row = 0;
         let mut col = 0;
 
         for (i, c) in self.to_string().chars().enumerate() {
-            if i == self.get_cursor() {
+            if i == index {
                 break;
             }
             if c == '\n' {
@@ -126,6 +385,46 @@ impl GapBuffer {
         (row, col)
     }
 
+    // Returns the index of the given row and column, clamping the column to the
+    // end of the row and the row to the last line.
+    pub fn get_index_at_position(&self, row: usize, col: usize) -> usize {
+        let mut current_row = 0;
+        let mut current_col = 0;
+
+        for (i, c) in self.to_string().chars().enumerate() {
+            if current_row == row && (current_col == col || c == '\n' || c == EOF_CHAR) {
+                return i;
+            }
+            if c == '\n' {
+                current_row += 1;
+                current_col = 0;
+            } else {
+                current_col += 1;
+            }
+        }
+
+        self.length() - 1
+    }
+
+    pub fn get_char(&self, index: usize) -> char {
+        if index < self.gap_start {
+            self.buffer[index]
+        } else {
+            self.buffer[index + self.gap_end - self.gap_start]
+        }
+    }
+
+    pub fn get_line_count(&self) -> usize {
+        self.to_string().split('\n').count()
+    }
+
+    // Returns the text without the end-of-file marker, e.g. for saving it.
+    pub fn text(&self) -> String {
+        let mut text = self.to_string();
+        text.pop();
+        text
+    }
+
     pub fn to_string(&self) -> String {
         let mut result = String::with_capacity(self.buffer.len() - (self.gap_end - self.gap_start));
         result.extend(self.buffer[..self.gap_start].iter());
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use crate::{
+    gap_buffer::GapBuffer,
+    language::Language,
+    motions,
+    selection::{Edit, Selection, Selections},
+    settings,
+};
+
+// How a buffer indents its lines.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+impl IndentStyle {
+    // The text of one level of indentation.
+    pub fn unit(self) -> String {
+        match self {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces(width) => " ".repeat(width),
+        }
+    }
+
+    // How many columns one level takes up.
+    pub fn width(self) -> usize {
+        match self {
+            IndentStyle::Tabs => settings::tab_width,
+            IndentStyle::Spaces(width) => width,
+        }
+    }
+
+    // Guesses the style of a file from the indentation of its lines. More
+    // lines starting with a tab than with a space mean tabs. Otherwise the most
+    // common step between the indentation of one line and the next is the
+    // number of spaces.
+    pub fn detect(text: &str) -> Option<Self> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        // How often each step from 1 to 8 spaces was seen.
+        let mut steps = [0; 9];
+        let mut previous_width = 0;
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let width = line.chars().take_while(|&c| c == ' ').count();
+            if width > 0 {
+                space_lines += 1;
+            }
+            let step = width.abs_diff(previous_width);
+            if (1..steps.len()).contains(&step) {
+                steps[step] += 1;
+            }
+            previous_width = width;
+        }
+
+        if tab_lines > space_lines {
+            return Some(IndentStyle::Tabs);
+        }
+        if space_lines == 0 {
+            return None;
+        }
+        // Odd steps are usually alignment, e.g. the stars of a block comment.
+        let width = (2..steps.len()).max_by_key(|&step| (steps[step], step == settings::tab_width))?;
+        Some(IndentStyle::Spaces(if steps[width] > 0 { width } else { settings::tab_width }))
+    }
+}
+
+impl fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndentStyle::Tabs => write!(f, "Tabs"),
+            IndentStyle::Spaces(width) => write!(f, "Spaces: {}", width),
+        }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn closes(opener: char, closer: char) -> bool {
+    matches!((opener, closer), ('{', '}') | ('[', ']') | ('(', ')'))
+}
+
+// The edit Enter makes at a selection. The line break replaces the selection
+// and the blanks around it, and the new line starts with the indentation of
+// the current one, one level deeper after an opening bracket or colon. Between
+// a pair of brackets the closer moves to a line of its own.
+pub fn newline(buffer: &GapBuffer, selection: &Selection, language: Option<&Language>, unit: &str) -> Edit {
+    let line_start = motions::line_start(buffer, selection.start());
+    let line_end = motions::line_end(buffer, selection.end());
+    let indent: String = (line_start..selection.start())
+        .map(|index| buffer.get_char(index))
+        .take_while(|&c| is_blank(c))
+        .collect();
+
+    let mut start = selection.start();
+    while start > line_start && is_blank(buffer.get_char(start - 1)) {
+        start -= 1;
+    }
+    let mut end = selection.end();
+    while end < line_end && is_blank(buffer.get_char(end)) {
+        end += 1;
+    }
+
+    let opener = (start > line_start)
+        .then(|| buffer.get_char(start - 1))
+        .filter(|c| language.is_some_and(|language| language.indent_after.contains(c)));
+    let Some(opener) = opener else {
+        let text = format!("\n{}", indent);
+        let length = text.chars().count();
+        return (start, end, text, length);
+    };
+
+    let text = format!("\n{}{}", indent, unit);
+    let length = text.chars().count();
+    if end < line_end && closes(opener, buffer.get_char(end)) {
+        return (start, end, format!("{}\n{}", text, indent), length);
+    }
+    (start, end, text, length)
+}
+
+// The edit typing a closing bracket makes at a selection. At the start of a
+// line it takes away one level of the indentation in front of it.
+pub fn type_closer(buffer: &GapBuffer, selection: &Selection, closer: char, unit: &str) -> Edit {
+    let text = closer.to_string();
+    let cursor = selection.head;
+    let line_start = motions::line_start(buffer, cursor);
+    if !selection.is_empty() || line_start == cursor || !(line_start..cursor).all(|index| is_blank(buffer.get_char(index))) {
+        return (selection.start(), selection.end(), text, 1);
+    }
+    // A tab is a whole level, spaces are taken away up to the width of one.
+    let removed = if buffer.get_char(cursor - 1) == '\t' {
+        1
+    } else {
+        (line_start..cursor)
+            .rev()
+            .take(unit.chars().count().max(1))
+            .take_while(|&index| buffer.get_char(index) == ' ')
+            .count()
+    };
+    (cursor - removed, cursor, text, 1)
+}
+
+// The edit Tab makes at a selection: a tab, or spaces up to the next indent stop.
+pub fn insert_indent(buffer: &GapBuffer, selection: &Selection, style: IndentStyle) -> Edit {
+    let text = match style {
+        IndentStyle::Tabs => "\t".to_string(),
+        IndentStyle::Spaces(width) => {
+            let column = selection.start() - motions::line_start(buffer, selection.start());
+            " ".repeat(width - column % width)
+        }
+    };
+    let length = text.chars().count();
+    (selection.start(), selection.end(), text, length)
+}
+
+// The edit Backspace makes at a selection. In the indentation of a line it
+// deletes spaces back to the previous indent stop.
+pub fn backspace(buffer: &GapBuffer, selection: &Selection, style: IndentStyle) -> Edit {
+    let cursor = selection.head;
+    let line_start = motions::line_start(buffer, cursor);
+    let in_indentation = (line_start..cursor).all(|index| buffer.get_char(index) == ' ');
+    if !selection.is_empty() || cursor == line_start || !in_indentation || style == IndentStyle::Tabs {
+        let start = if selection.is_empty() { cursor.saturating_sub(1) } else { selection.start() };
+        return (start, selection.end(), String::new(), 0);
+    }
+    let width = style.width().max(1);
+    let column = cursor - line_start;
+    let removed = (column - 1) % width + 1;
+    (cursor - removed, cursor, String::new(), 0)
+}
+
+// Indents the lines that have a selection or cursor on them by one level, or
+// outdents them. Blank lines are not indented. The selections grow and shrink
+// with their lines, and the change is a single undo step.
+pub fn shift_lines(buffer: &mut GapBuffer, selections: &mut Selections, style: IndentStyle, outdent: bool) {
+    let rows = selections.rows(buffer);
+
+    // Where each line changes, how many characters go and what comes in.
+    let unit = style.unit();
+    let mut edits: Vec<(usize, usize, &str)> = Vec::new();
+    for row in rows {
+        let start = buffer.get_index_at_position(row, 0);
+        let end = motions::line_end(buffer, start);
+        if outdent {
+            let removed = if start < end && buffer.get_char(start) == '\t' {
+                1
+            } else {
+                (start..end).take(style.width()).take_while(|&index| buffer.get_char(index) == ' ').count()
+            };
+            if removed > 0 {
+                edits.push((start, removed, ""));
+            }
+        } else if (start..end).any(|index| !is_blank(buffer.get_char(index))) {
+            edits.push((start, 0, &unit));
+        }
+    }
+    selections.edit_around(buffer, &edits);
+}
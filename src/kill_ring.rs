@@ -0,0 +1,56 @@
+use crate::settings;
+
+// Killed text for yanking back, most recent last, as in Emacs. Consecutive
+// kills add up to a single entry, and a yank can be swapped for older entries.
+pub struct KillRing {
+    entries: Vec<String>,
+    // The entry the last yank inserted, and its length in characters.
+    yanked: Option<(usize, usize)>,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        KillRing {
+            entries: Vec::new(),
+            yanked: None,
+        }
+    }
+
+    fn push(&mut self, text: String) {
+        self.entries.push(text);
+        if self.entries.len() > settings::KILL_RING_SIZE {
+            self.entries.remove(0);
+        }
+    }
+
+    // Adds killed text, to the last entry if `append` is set because the
+    // previous command was a kill too. Returns the entry it went into.
+    pub fn kill(&mut self, text: &str, append: bool) -> &str {
+        match self.entries.last_mut() {
+            Some(last) if append => last.push_str(text),
+            _ => self.push(text.to_string()),
+        }
+        self.entries.last().unwrap()
+    }
+
+    // Returns the text to yank. Text copied outside the editor since the last
+    // kill is added first, so it is yanked instead.
+    pub fn yank(&mut self, clipboard_text: Option<String>) -> Option<String> {
+        if let Some(text) = clipboard_text.filter(|text| self.entries.last() != Some(text)) {
+            self.push(text);
+        }
+        let text = self.entries.last()?.clone();
+        self.yanked = Some((self.entries.len() - 1, text.chars().count()));
+        Some(text)
+    }
+
+    // Moves on to the entry before the one yanked last, wrapping around to the
+    // newest. Returns the length of the yanked text to replace, and the entry.
+    pub fn yank_pop(&mut self) -> Option<(usize, String)> {
+        let (index, length) = self.yanked?;
+        let index = index.checked_sub(1).unwrap_or(self.entries.len() - 1);
+        let text = self.entries[index].clone();
+        self.yanked = Some((index, text.chars().count()));
+        Some((length, text))
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+// What the editor knows about a programming language, picked by the file extension.
+pub struct Language {
+    pub extensions: &'static [&'static str],
+    // Characters that indent the next line when a line ends with them.
+    pub indent_after: &'static [char],
+    // Characters that dedent their line when typed at its start.
+    pub dedent_on: &'static [char],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_LINE_COMMENT: Option<&str> = Some("//");
+const C_BLOCK_COMMENT: Option<(&str, &str)> = Some(("/*", "*/"));
+
+const BRACKETS: &[char] = &['{', '[', '('];
+const CLOSING_BRACKETS: &[char] = &['}', ']', ')'];
+
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["c", "h"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["cpp", "cc", "cxx", "hpp", "hh"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["cs"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["java"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["go"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["js", "jsx", "mjs", "cjs"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["ts", "tsx"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: C_LINE_COMMENT,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["json"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: None,
+        block_comment: None,
+    },
+    Language {
+        extensions: &["css", "scss"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: None,
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["py", "pyw"],
+        indent_after: &[':', '{', '[', '('],
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: Some("#"),
+        block_comment: Some(("\"\"\"", "\"\"\"")),
+    },
+    Language {
+        extensions: &["yml", "yaml"],
+        indent_after: &[':'],
+        dedent_on: &[],
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+    Language {
+        extensions: &["sh", "bash", "zsh"],
+        indent_after: &[],
+        dedent_on: &[],
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+    Language {
+        extensions: &["toml"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+    Language {
+        extensions: &["lua"],
+        indent_after: BRACKETS,
+        dedent_on: CLOSING_BRACKETS,
+        line_comment: Some("--"),
+        block_comment: Some(("--[[", "]]")),
+    },
+    Language {
+        extensions: &["sql"],
+        indent_after: &['('],
+        dedent_on: &[')'],
+        line_comment: Some("--"),
+        block_comment: C_BLOCK_COMMENT,
+    },
+    Language {
+        extensions: &["html", "htm", "xml", "svg"],
+        indent_after: &[],
+        dedent_on: &[],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+    },
+];
+
+pub fn language_for(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension.as_str()))
+}
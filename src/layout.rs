@@ -0,0 +1,163 @@
+use sdl2::{rect::Rect, ttf::Font};
+
+use crate::settings;
+
+// Screen regions of the editor window. Recomputed every frame from the window
+// size and the bars and panels that are open. The editor area holds the panes,
+// with the sidebar to its left.
+#[derive(Clone, Copy)]
+pub struct Layout {
+    pub tab_bar: Rect,
+    pub sidebar: Option<Rect>,
+    pub editor: Rect,
+    pub find_bar: Option<Rect>,
+    pub bottom_panel: Option<Rect>,
+    pub status_bar: Rect,
+}
+
+impl Layout {
+    pub fn new(
+        (window_width, window_height): (u32, u32),
+        tab_bar_height: u32,
+        status_bar_height: u32,
+        sidebar_width: Option<u32>,
+        find_bar_height: Option<u32>,
+        bottom_panel_height: Option<u32>,
+    ) -> Self {
+        let tab_bar_height = tab_bar_height.min(window_height);
+        // The rest of the layout fits into the height left below the tab bar.
+        let window_height = window_height - tab_bar_height;
+        let status_bar_height = status_bar_height.min(window_height);
+        let find_bar_height = find_bar_height.unwrap_or(0).min(window_height - status_bar_height);
+        let bottom_panel_height = bottom_panel_height
+            .unwrap_or(0)
+            .min(window_height - status_bar_height - find_bar_height);
+        let content_height = window_height - status_bar_height - find_bar_height - bottom_panel_height;
+        // Everything below the tab bar is laid out from here down.
+        let top = tab_bar_height as i32;
+        // Leave the editor at least half of the window.
+        let sidebar_width = sidebar_width.unwrap_or(0).min(window_width / 2);
+
+        Layout {
+            tab_bar: Rect::new(0, 0, window_width, tab_bar_height.max(1)),
+            sidebar: (sidebar_width > 0).then(|| Rect::new(0, top, sidebar_width, content_height.max(1))),
+            editor: Rect::new(
+                sidebar_width as i32,
+                top,
+                (window_width - sidebar_width).max(1),
+                content_height.max(1),
+            ),
+            find_bar: (find_bar_height > 0)
+                .then(|| Rect::new(0, top + content_height as i32, window_width, find_bar_height)),
+            bottom_panel: (bottom_panel_height > 0).then(|| {
+                Rect::new(
+                    0,
+                    top + (content_height + find_bar_height) as i32,
+                    window_width,
+                    bottom_panel_height,
+                )
+            }),
+            status_bar: Rect::new(
+                0,
+                top + (content_height + find_bar_height + bottom_panel_height) as i32,
+                window_width,
+                status_bar_height,
+            ),
+        }
+    }
+}
+
+// Regions of one pane, and how far its text can be scrolled. The scroll bars
+// are hidden while the text fits into the view.
+#[derive(Clone, Copy)]
+pub struct PaneLayout {
+    pub area: Rect,
+    pub gutter: Rect,
+    pub text_area: Rect,
+    pub vertical_scroll_bar: Option<Rect>,
+    pub horizontal_scroll_bar: Option<Rect>,
+    pub max_scroll: (u32, u32),
+}
+
+impl PaneLayout {
+    pub fn new(area: Rect, gutter_width: u32, (text_width, text_height): (u32, u32)) -> Self {
+        let gutter_width = gutter_width.min(area.width());
+        let content_width = area.width() - gutter_width;
+        let content_height = area.height();
+
+        // Showing one scroll bar takes space away from the other direction, which
+        // may in turn make the other bar necessary.
+        let mut show_vertical = text_height > content_height;
+        let mut show_horizontal = text_width > content_width;
+        if show_vertical && !show_horizontal {
+            show_horizontal = text_width > content_width.saturating_sub(settings::SCROLL_BAR_WIDTH);
+        }
+        if show_horizontal && !show_vertical {
+            show_vertical = text_height > content_height.saturating_sub(settings::SCROLL_BAR_HEIGHT);
+        }
+
+        let text_area_width = if show_vertical {
+            content_width.saturating_sub(settings::SCROLL_BAR_WIDTH)
+        } else {
+            content_width
+        };
+        let text_area_height = if show_horizontal {
+            content_height.saturating_sub(settings::SCROLL_BAR_HEIGHT)
+        } else {
+            content_height
+        };
+        let text_x = area.x() + gutter_width as i32;
+        let top = area.y();
+
+        PaneLayout {
+            area,
+            gutter: Rect::new(area.x(), top, gutter_width.max(1), text_area_height.max(1)),
+            text_area: Rect::new(text_x, top, text_area_width.max(1), text_area_height.max(1)),
+            vertical_scroll_bar: show_vertical.then(|| {
+                Rect::new(
+                    text_x + text_area_width as i32,
+                    top,
+                    settings::SCROLL_BAR_WIDTH,
+                    text_area_height.max(1),
+                )
+            }),
+            horizontal_scroll_bar: show_horizontal.then(|| {
+                Rect::new(
+                    text_x,
+                    top + text_area_height as i32,
+                    text_area_width.max(1),
+                    settings::SCROLL_BAR_HEIGHT,
+                )
+            }),
+            max_scroll: (
+                text_width.saturating_sub(text_area_width),
+                text_height.saturating_sub(text_area_height),
+            ),
+        }
+    }
+
+    // Converts a point in window coordinates into text coordinates.
+    pub fn text_position(&self, (x, y): (i32, i32), (scroll_x, scroll_y): (i32, i32)) -> (i32, i32) {
+        (x - self.text_area.x() + scroll_x, y - self.text_area.y() + scroll_y)
+    }
+}
+
+// Returns a box of the given size centered horizontally near the top of
+// `area`, shrunk to fit if needed. Used for overlays like the file finder.
+pub fn overlay_rect(area: Rect, (width, height): (u32, u32)) -> Rect {
+    let width = width.min(area.width());
+    let height = height.min(area.height());
+    let x = area.x() + (area.width() - width) as i32 / 2;
+    let y = area.y() + (area.height() - height).min(settings::OVERLAY_MARGIN) as i32;
+    Rect::new(x, y, width.max(1), height.max(1))
+}
+
+pub fn gutter_width(font: &Font, line_count: usize) -> u32 {
+    let digits = line_count.max(1).to_string().len() as u32;
+    let digit_width = font.size_of_char('0').unwrap().0;
+    digits * digit_width + 2 * settings::GUTTER_PADDING
+}
+
+pub fn status_bar_height(font: &Font) -> u32 {
+    font.height() as u32 + 2 * settings::STATUS_BAR_PADDING
+}
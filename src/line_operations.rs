@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+
+use crate::{
+    commands::{CaseChange, SortOptions},
+    gap_buffer::GapBuffer,
+    motions,
+    selection::{Edit, Selection, Selections},
+};
+
+// The runs of consecutive rows that have a selection or cursor on them, as
+// first and last row.
+fn line_blocks(buffer: &GapBuffer, selections: &Selections) -> Vec<(usize, usize)> {
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for row in selections.rows(buffer) {
+        match blocks.last_mut() {
+            Some((_, last)) if *last + 1 == row => *last = row,
+            _ => blocks.push((row, row)),
+        }
+    }
+    blocks
+}
+
+// The part of the text taken up by the rows `first..=last`, without the line
+// break after the last one.
+fn block_range(buffer: &GapBuffer, (first, last): (usize, usize)) -> (usize, usize) {
+    let start = buffer.get_index_at_position(first, 0);
+    let end = motions::line_end(buffer, buffer.get_index_at_position(last, 0));
+    (start, end)
+}
+
+fn block_lines(buffer: &GapBuffer, block: (usize, usize)) -> Vec<String> {
+    let (start, end) = block_range(buffer, block);
+    let text: String = (start..end).map(|index| buffer.get_char(index)).collect();
+    text.split('\n').map(str::to_string).collect()
+}
+
+// Replaces each block of rows with the lines `f` makes of it, as a single undo
+// step. Returns where each block's new text is. A block replaced by no lines is
+// deleted along with a line break next to it.
+fn replace_blocks(
+    buffer: &mut GapBuffer,
+    blocks: &[(usize, usize)],
+    f: impl Fn(Vec<String>) -> Vec<String>,
+) -> Vec<(usize, usize)> {
+    let edits: Vec<(usize, usize, String)> = blocks
+        .iter()
+        .map(|&block| {
+            let (mut start, mut end) = block_range(buffer, block);
+            let lines = f(block_lines(buffer, block));
+            if lines.is_empty() {
+                if end < buffer.length() - 1 {
+                    end += 1;
+                } else {
+                    start = start.saturating_sub(1);
+                }
+            }
+            (start, end, lines.join("\n"))
+        })
+        .collect();
+
+    buffer.begin_undo_group();
+    for (start, end, text) in edits.iter().rev() {
+        buffer.delete_range(*start, *end);
+        buffer.insert_str(*start, text);
+    }
+    buffer.end_undo_group();
+
+    // Earlier blocks shift the ones after them.
+    let mut shift: isize = 0;
+    edits
+        .iter()
+        .map(|(start, end, text)| {
+            let length = text.chars().count();
+            let new_start = (*start as isize + shift) as usize;
+            shift += length as isize - (end - start) as isize;
+            (new_start, new_start + length)
+        })
+        .collect()
+}
+
+// Duplicates each block of lines with a cursor or selection on them below
+// itself, once however many selections it has. When every selection is within
+// a line, each is duplicated after itself instead.
+pub fn duplicate(buffer: &mut GapBuffer, selections: &mut Selections) {
+    let within_lines = selections.iter().all(|selection| {
+        !selection.is_empty() && !(selection.start()..selection.end()).any(|index| buffer.get_char(index) == '\n')
+    });
+    if within_lines {
+        selections.edit_with(buffer, |buffer, selection| {
+            let selected: String = (selection.start()..selection.end()).map(|index| buffer.get_char(index)).collect();
+            let length = selected.chars().count();
+            (selection.end(), selection.end(), selected, length)
+        });
+        return;
+    }
+
+    // The copy goes above each block, so the selections move down with the
+    // original text and keep their places on the lower of the two.
+    let copies: Vec<(usize, String)> = line_blocks(buffer, selections)
+        .into_iter()
+        .map(|block| (block_range(buffer, block).0, format!("{}\n", block_lines(buffer, block).join("\n"))))
+        .collect();
+    buffer.begin_undo_group();
+    for (start, text) in copies.iter().rev() {
+        buffer.insert_str(*start, text);
+    }
+    buffer.end_undo_group();
+
+    let map = |position: usize| -> usize {
+        let shift: usize = copies
+            .iter()
+            .filter(|(start, _)| *start <= position)
+            .map(|(_, text)| text.chars().count())
+            .sum();
+        position + shift
+    };
+    selections.map_each(buffer, |selection| Selection::range(map(selection.anchor), map(selection.head)));
+}
+
+pub fn delete(buffer: &mut GapBuffer, selections: &mut Selections) {
+    let blocks = line_blocks(buffer, selections);
+    let ranges = replace_blocks(buffer, &blocks, |_| Vec::new());
+    let cursors: Vec<usize> = ranges.iter().map(|&(start, _)| motions::line_start(buffer, start.min(buffer.length() - 1))).collect();
+    selections.set_cursors(buffer, &cursors);
+}
+
+// Swaps the lines of each block with the line above or below it. The
+// selections move along with their lines.
+pub fn move_lines(buffer: &mut GapBuffer, selections: &mut Selections, up: bool) {
+    let last_row = buffer.get_line_count() - 1;
+    let blocks = line_blocks(buffer, selections);
+    if blocks.first().is_some_and(|&(first, _)| up && first == 0) || blocks.last().is_some_and(|&(_, last)| !up && last == last_row) {
+        return;
+    }
+
+    // How far each block's text moves, and the blocks with the line they swap with.
+    let mut moves: Vec<(usize, usize, isize)> = Vec::new();
+    let mut swaps: Vec<(usize, usize)> = Vec::new();
+    for &(first, last) in &blocks {
+        let (start, end) = block_range(buffer, (first, last));
+        if up {
+            let (other_start, _) = block_range(buffer, (first - 1, first - 1));
+            moves.push((start, end, other_start as isize - start as isize));
+            swaps.push((first - 1, last));
+        } else {
+            let (other_start, other_end) = block_range(buffer, (last + 1, last + 1));
+            moves.push((start, end, (other_end - other_start + 1) as isize));
+            swaps.push((first, last + 1));
+        }
+    }
+
+    replace_blocks(buffer, &swaps, |mut lines| {
+        if up {
+            lines.rotate_left(1);
+        } else {
+            lines.rotate_right(1);
+        }
+        lines
+    });
+
+    // The end of a selection right after a block moves with it too.
+    let map = |position: usize| {
+        moves
+            .iter()
+            .find(|&&(start, end, _)| (start..=end + 1).contains(&position))
+            .map_or(position, |&(_, _, offset)| (position as isize + offset) as usize)
+    };
+    selections.map_each(buffer, |selection| Selection::range(map(selection.anchor), map(selection.head)));
+}
+
+// Joins each line with the next one, or the lines of a multi-line selection,
+// replacing the indentation between them with a single space.
+pub fn join(buffer: &mut GapBuffer, selections: &mut Selections) {
+    let last_row = buffer.get_line_count() - 1;
+    let blocks: Vec<(usize, usize)> = line_blocks(buffer, selections)
+        .into_iter()
+        .map(|(first, last)| (first, if first == last { (last + 1).min(last_row) } else { last }))
+        .collect();
+    // The cursors go where the first line meets the next.
+    let first_lengths: Vec<usize> = blocks
+        .iter()
+        .map(|&(first, _)| block_lines(buffer, (first, first))[0].trim_end().chars().count())
+        .collect();
+    let ranges = replace_blocks(buffer, &blocks, |lines| {
+        let mut joined = String::new();
+        for line in lines {
+            let line = if joined.is_empty() { line.trim_end() } else { line.trim() };
+            if !joined.is_empty() && !line.is_empty() {
+                joined.push(' ');
+            }
+            joined.push_str(line);
+        }
+        vec![joined]
+    });
+    let cursors: Vec<usize> = ranges.iter().zip(first_lengths).map(|(&(start, _), length)| start + length).collect();
+    selections.set_cursors(buffer, &cursors);
+}
+
+// The rows a sort or reverse works on: the selected lines, or the whole text
+// if there is only a single line selected.
+fn rows_to_reorder(buffer: &GapBuffer, selections: &Selections) -> Vec<(usize, usize)> {
+    let blocks = line_blocks(buffer, selections);
+    if blocks.iter().all(|&(first, last)| first == last) {
+        // Leave out the empty line after a final line break.
+        let mut last = buffer.get_line_count() - 1;
+        if last > 0 && buffer.get_index_at_position(last, 0) == buffer.length() - 1 {
+            last -= 1;
+        }
+        return vec![(0, last)];
+    }
+    blocks.into_iter().filter(|&(first, last)| first < last).collect()
+}
+
+fn select_blocks(buffer: &mut GapBuffer, selections: &mut Selections, ranges: &[(usize, usize)]) {
+    let selected: Vec<Selection> = ranges.iter().map(|&(start, end)| Selection::range(start, end)).collect();
+    selections.set_all(buffer, selected);
+}
+
+// The number a line starts with, for sorting numerically.
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let length = line
+        .char_indices()
+        .take_while(|&(index, c)| c.is_ascii_digit() || c == '.' || (index == 0 && c == '-'))
+        .count();
+    line[..length].parse().ok()
+}
+
+pub fn sort(buffer: &mut GapBuffer, selections: &mut Selections, options: SortOptions) {
+    let compare = |a: &String, b: &String| {
+        let by_number = if options.numeric {
+            // Lines without a number come first.
+            match (leading_number(a), leading_number(b)) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            }
+        } else {
+            Ordering::Equal
+        };
+        let by_text = if options.ignore_case {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        };
+        by_number.then(by_text)
+    };
+
+    let blocks = rows_to_reorder(buffer, selections);
+    let ranges = replace_blocks(buffer, &blocks, |mut lines| {
+        lines.sort_by(compare);
+        if options.unique {
+            lines.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+        }
+        lines
+    });
+    select_blocks(buffer, selections, &ranges);
+}
+
+pub fn reverse(buffer: &mut GapBuffer, selections: &mut Selections) {
+    let blocks = rows_to_reorder(buffer, selections);
+    let ranges = replace_blocks(buffer, &blocks, |mut lines| {
+        lines.reverse();
+        lines
+    });
+    select_blocks(buffer, selections, &ranges);
+}
+
+fn change_case(text: &str, change: CaseChange) -> String {
+    match change {
+        CaseChange::Upper => text.to_uppercase(),
+        CaseChange::Lower => text.to_lowercase(),
+        CaseChange::Swap => text
+            .chars()
+            .flat_map(|c| -> Vec<char> {
+                if c.is_uppercase() {
+                    c.to_lowercase().collect()
+                } else {
+                    c.to_uppercase().collect()
+                }
+            })
+            .collect(),
+        CaseChange::Title => {
+            let mut result = String::with_capacity(text.len());
+            let mut previous_is_word = false;
+            for c in text.chars() {
+                if previous_is_word {
+                    result.extend(c.to_lowercase());
+                } else {
+                    result.extend(c.to_uppercase());
+                }
+                previous_is_word = c.is_alphanumeric() || c == '\'';
+            }
+            result
+        }
+    }
+}
+
+// Changes the case of each selection, or of the word at each cursor. The
+// selections stay on the changed text.
+pub fn set_case(buffer: &mut GapBuffer, selections: &mut Selections, change: CaseChange) {
+    let before: Vec<Selection> = selections.iter().copied().collect();
+    let targets: Vec<Selection> = selections
+        .iter()
+        .map(|selection| {
+            if selection.is_empty() {
+                let (start, end) = motions::word_at(buffer, selection.head);
+                Selection::range(start, end)
+            } else {
+                *selection
+            }
+        })
+        .collect();
+    // Cursors in the same word change it once.
+    selections.set_all(buffer, targets);
+    let edits: Vec<Edit> = selections
+        .iter()
+        .map(|target| {
+            let text: String = (target.start()..target.end()).map(|index| buffer.get_char(index)).collect();
+            let text = change_case(&text, change);
+            let length = text.chars().count();
+            (target.start(), target.end(), text, length)
+        })
+        .collect();
+    // Case changes that keep the length, which is nearly all of them, keep the selections too.
+    let same_lengths = edits.iter().all(|(start, end, _, length)| end - start == *length);
+
+    selections.edit_all(buffer, edits);
+    if same_lengths {
+        selections.set_all(buffer, before);
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{
+    commands::{command_by_name, Command, COMMANDS},
+    fuzzy::fuzzy_score,
+    settings,
+    vim::VimKey,
+};
+
+const MACROS_FILE: &str = "macros";
+
+// One recorded step of a keyboard macro. Keys are recorded as what they did,
+// so a macro replays the same way whatever the keybindings are.
+#[derive(Clone, PartialEq)]
+pub enum MacroStep {
+    Command(Command),
+    Text(String),
+    Vim(VimKey),
+}
+
+// What the macro prompt asks for.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MacroPrompt {
+    ReplayTimes,
+    Save,
+    Play,
+}
+
+// A macro being replayed. It runs a repetition at a time, so even a large
+// count neither holds every step at once nor freezes the editor.
+pub struct Replay {
+    steps: Vec<MacroStep>,
+    times: usize,
+}
+
+impl Replay {
+    // Takes the steps of the next repetition, or returns None once all ran.
+    pub fn next_repetition(&mut self) -> Option<Vec<MacroStep>> {
+        if self.times == 0 || self.steps.is_empty() {
+            return None;
+        }
+        self.times -= 1;
+        Some(self.steps.clone())
+    }
+}
+
+pub struct Macros {
+    // The steps recorded so far while recording.
+    recording: Option<Vec<MacroStep>>,
+    // The macro replayed by Replay Macro, the last one recorded or played.
+    last: Vec<MacroStep>,
+    saved: Vec<(String, Vec<MacroStep>)>,
+    pub prompt: Option<(MacroPrompt, String)>,
+    pub scroll: usize,
+    selected: usize,
+}
+
+fn macros_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(MACROS_FILE))
+}
+
+fn command_name(command: Command) -> &'static str {
+    COMMANDS.iter().find(|info| info.command == command).map_or("", |info| info.name)
+}
+
+// Text is written on one line, with backslashes and line breaks escaped.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+// Reads the saved macros. Each starts with its name in brackets, followed by
+// one step per line: `command <name>`, `text <text>` or `vim <key>`.
+fn parse_macros(contents: &str) -> Vec<(String, Vec<MacroStep>)> {
+    let mut macros: Vec<(String, Vec<MacroStep>)> = Vec::new();
+    for (number, line) in contents.lines().enumerate().map(|(index, line)| (index + 1, line)) {
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            macros.push((name.to_string(), Vec::new()));
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (kind, value) = line.split_once(' ').unwrap_or((line, ""));
+        let step = match kind {
+            "command" => command_by_name(value).map(MacroStep::Command),
+            "text" => Some(MacroStep::Text(unescape(value))),
+            "vim" => VimKey::parse(value).map(MacroStep::Vim),
+            _ => None,
+        };
+        match (step, macros.last_mut()) {
+            (Some(step), Some((_, steps))) => steps.push(step),
+            _ => eprintln!("Macros: skipped line {}", number),
+        }
+    }
+    macros
+}
+
+impl Macros {
+    pub fn load() -> Self {
+        let saved = macros_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map_or(Vec::new(), |contents| parse_macros(&contents));
+        Macros {
+            recording: None,
+            last: Vec::new(),
+            saved,
+            prompt: None,
+            scroll: 0,
+            selected: 0,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Starts recording, or stops and keeps what was recorded for replaying.
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(steps) => self.last = steps,
+            None => self.recording = Some(Vec::new()),
+        }
+    }
+
+    pub fn record(&mut self, step: MacroStep) {
+        let Some(steps) = self.recording.as_mut() else {
+            return;
+        };
+        // Text typed in a row is kept as a single step.
+        match (steps.last_mut(), &step) {
+            (Some(MacroStep::Text(text)), MacroStep::Text(more)) => text.push_str(more),
+            _ => steps.push(step),
+        }
+    }
+
+    // Replays the last macro `times` times. Nothing is replayed while
+    // recording, so a macro cannot replay itself.
+    pub fn replay(&self, times: usize) -> Option<Replay> {
+        if self.is_recording() {
+            return None;
+        }
+        Some(Replay {
+            steps: self.last.clone(),
+            times,
+        })
+    }
+
+    pub fn start_prompt(&mut self, prompt: MacroPrompt) {
+        self.prompt = Some((prompt, String::new()));
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    // Changes the typed text, selecting the best match again.
+    pub fn edit_prompt(&mut self, edit: impl FnOnce(&mut String)) {
+        if let Some((_, text)) = self.prompt.as_mut() {
+            edit(text);
+            self.selected = 0;
+            self.scroll = 0;
+        }
+    }
+
+    // The names of the saved macros matching the typed name, best match first.
+    pub fn matches(&self) -> Vec<String> {
+        let query = self.prompt.as_ref().map_or("", |(_, text)| text.as_str());
+        let mut scored: Vec<(i32, &String)> = self
+            .saved
+            .iter()
+            .filter_map(|(name, _)| Some((fuzzy_score(query, name)?, name)))
+            .collect();
+        scored.sort_by_key(|&(score, name)| (-score, name.clone()));
+        scored.into_iter().map(|(_, name)| name.clone()).collect()
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.matches().len().saturating_sub(1));
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.select((self.selected as isize + offset).max(0) as usize);
+    }
+
+    // Carries out the prompt, returning the macro to replay if there is one.
+    // The prompt stays open if the typed text is not usable.
+    pub fn confirm_prompt(&mut self) -> Option<Replay> {
+        let (prompt, text) = self.prompt.clone()?;
+        let text = text.trim();
+        let replay = match prompt {
+            MacroPrompt::ReplayTimes => self.replay(text.parse::<usize>().ok()?),
+            MacroPrompt::Save => {
+                if text.is_empty() || text.contains(['[', ']']) {
+                    return None;
+                }
+                if !self.last.is_empty() {
+                    self.saved.retain(|(name, _)| name != text);
+                    self.saved.push((text.to_string(), self.last.clone()));
+                    self.save();
+                }
+                None
+            }
+            MacroPrompt::Play => {
+                let name = self.matches().into_iter().nth(self.selected)?;
+                let (_, steps) = self.saved.iter().find(|(saved, _)| *saved == name).unwrap();
+                // The played macro becomes the one replayed next.
+                self.last = steps.clone();
+                self.replay(1)
+            }
+        };
+        self.prompt = None;
+        replay
+    }
+
+    fn save(&self) {
+        let Some(path) = macros_path() else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                eprintln!("Unable to create config directory: {:?}", e);
+                return;
+            }
+        }
+
+        let mut contents = String::new();
+        for (name, steps) in &self.saved {
+            contents.push_str(&format!("[{}]\n", name));
+            for step in steps {
+                contents.push_str(&match step {
+                    MacroStep::Command(command) => format!("command {}\n", command_name(*command)),
+                    MacroStep::Text(text) => format!("text {}\n", escape(text)),
+                    MacroStep::Vim(key) => format!("vim {}\n", key),
+                });
+            }
+        }
+        if let Err(e) = fs::write(&path, contents) {
+            eprintln!("Unable to save macros: {:?}", e);
+        }
+    }
+}
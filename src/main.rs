@@ -1,27 +1,73 @@
+mod block_selection;
+mod brackets;
+mod clipboard;
+mod command_palette;
+mod commands;
+mod comments;
+mod completion;
 mod constants;
+mod document;
 mod event_handling;
+mod file_explorer;
+mod file_finder;
+mod find;
+mod fuzzy;
 mod gap_buffer;
+mod indentation;
+mod kill_ring;
+mod language;
+mod layout;
+mod line_operations;
+mod macros;
+mod motions;
+mod panes;
+mod project_search;
 mod rendering;
+mod scrolling;
+mod selection;
+mod session;
 mod settings;
+mod vim;
+use block_selection::BlockSelection;
+use clipboard::Clipboard;
+use command_palette::CommandPalette;
+use commands::{Command, Motion};
+use completion::{Completion, OtherWords};
+use event_handling::{KeyResult, Keymap, KeymapProfile};
+use document::{Document, Documents};
+use file_explorer::{ExplorerAction, ExplorerChange, FileExplorer};
+use file_finder::FileFinder;
+use find::{Find, FindField};
+use indentation::IndentStyle;
+use kill_ring::KillRing;
+use macros::{MacroPrompt, MacroStep, Macros, Replay};
+use layout::{gutter_width, overlay_rect, status_bar_height, Layout, PaneLayout};
+use panes::{Panes, SplitDirection};
+use project_search::ProjectSearch;
 use rendering::{
-    get_cursor_position, get_text_size, render_cursor, render_scroll_bars, render_text, get_nearest_character_position,
+    get_cursor_position, get_text_size, render_block_selection, render_cursor, render_divider, render_find_bar, render_gutter, render_list_panel, render_preview, render_tab_bar,
+    get_list_item_at, render_completion, FindBarRow, ListItem,
+    render_highlights, render_scroll_bars, render_selections, render_status_bar, render_text,
+    get_nearest_character_position, get_nearest_grid_position, ScrollBarMarker,
 };
 use sdl2::{
     self,
     event::{Event, WindowEvent},
-    keyboard::Keycode,
+    keyboard::{Keycode, Mod},
+    mouse::MouseWheelDirection,
     pixels::Color,
     rect::{Rect, Point},
 };
 use native_dialog::{FileDialog, MessageDialog, MessageType};
+use scrolling::{recenter, scroll_to_cursor, Recenter};
+use selection::Selection;
 use settings::{WINDOW_WIDTH, WINDOW_HEIGHT};
-use std::{fs::File, io::Write};
-use std::io::Read;
+use vim::{Vim, VimKey};
 use std::env;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 //TODO: Implement Delete Method
-//TODO: Implement Insert Mode
 //TODO: Implement Copy and Paste
 //TODO: Implement loading and saving a file.
 //TODO: Check if contents are connected to a file path
@@ -29,6 +75,40 @@ use std::time::{Duration, Instant};
 //TODO: Click to move cursor
 //TODO: Implement changing font size
 
+// Asks before closing a document with unsaved changes.
+fn confirm_close(document: &Document) -> bool {
+    document.has_been_saved
+        || document.buffer.length() == 1
+        || MessageDialog::new()
+            .set_type(MessageType::Warning)
+            .set_title("Unsaved changes")
+            .set_text(&format!("{} has unsaved changes. Close it anyway?", document.name()))
+            .show_confirm()
+            .unwrap_or(false)
+}
+
+// Types text at each cursor, or in place of the block selection.
+fn type_text(document: &mut Document, block_selection: &mut Option<BlockSelection>, text: &str) {
+    if let Some(block) = block_selection.take() {
+        let positions = block.replace(&mut document.buffer, &[text]);
+        document.selections.set_cursors(&mut document.buffer, &positions);
+        document.has_been_saved = false;
+    } else {
+        document.type_text(text);
+    }
+}
+
+// Asks before deleting a file or folder from the file explorer.
+fn confirm_delete(path: &Path) -> bool {
+    let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+    MessageDialog::new()
+        .set_type(MessageType::Warning)
+        .set_title("Delete")
+        .set_text(&format!("Delete {}? This cannot be undone.", name))
+        .show_confirm()
+        .unwrap_or(false)
+}
+
 pub fn main() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context
@@ -36,15 +116,24 @@ pub fn main() {
         .expect("Failed to initialize video subsystem/");
     let ttf_context = sdl2::ttf::init().expect("Failed to initialize ttf context.");
 
-    let window = video_subsystem
-        .window("Text Editor", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        // .resizable()
+    let window_state = session::load_window_state();
+    let mut window_builder = match &window_state {
+        Some(state) => video_subsystem.window("Text Editor", state.width, state.height),
+        None => video_subsystem.window("Text Editor", WINDOW_WIDTH, WINDOW_HEIGHT),
+    };
+    match &window_state {
+        Some(state) => window_builder.position(state.x, state.y),
+        None => window_builder.position_centered(),
+    };
+    let mut window = window_builder
+        .resizable()
         .build()
         .expect("Failed to build window");
+    window
+        .set_minimum_size(settings::MIN_WINDOW_WIDTH, settings::MIN_WINDOW_HEIGHT)
+        .expect("Failed to set minimum window size");
 
-    let (mut window_width, mut window_height) = window.size();
-    (window_width, window_height) = (window_width - settings::SCROLL_BAR_WIDTH, window_height - settings::SCROLL_BAR_HEIGHT);
+    let mut window_size = window.size();
 
     let mut canvas = window
         .into_canvas()
@@ -55,197 +144,781 @@ pub fn main() {
         .load_font(constants::FONT_PATH, settings::font_size)
         .expect("Failed to load font.");
 
-    let mut buffer = gap_buffer::GapBuffer::new(1024);
+    let mut documents = Documents::new();
+    let mut tabs: Vec<(Rect, Rect)> = Vec::new();
+    let mut block_selection: Option<BlockSelection> = None;
+    let mut selecting_with_mouse = false;
+    let mut clipboard = Clipboard::new(video_subsystem.clipboard());
+
+    let mut find = Find::new();
+    let mut find_open = false;
+    // Where the cursor was when the find bar opened; typing searches from here.
+    let mut find_origin = 0;
+
+    let mut project_search = ProjectSearch::new();
+    let mut project_search_open = false;
+    let mut project_search_scroll = 0;
+    let project_root = env::current_dir().unwrap();
+
+    let mut file_explorer = FileExplorer::new(&project_root);
+    let mut explorer_open = false;
+    // Whether the sidebar has the keyboard rather than the editor.
+    let mut explorer_focused = false;
+
+    let mut file_finder = FileFinder::new();
+    let mut file_finder_open = false;
+    // The finder's list and preview as drawn last frame, for handling clicks.
+    let mut file_finder_list: Option<Rect> = None;
 
-    let mut viewport_width = WINDOW_WIDTH;
-    let mut viewport_height = WINDOW_HEIGHT;
-    let mut viewport = Rect::new(0, 0, viewport_width, viewport_height);
-    canvas.set_viewport(Some(viewport));
+    let mut keymap = Keymap::load(if settings::EMACS_KEYBINDINGS { KeymapProfile::Emacs } else { KeymapProfile::Default });
+    // Set when a key was used up by a chord or a command, so the text it types is dropped too.
+    let mut swallow_text_input = false;
+
+    let mut kill_ring = KillRing::new();
+    // The last command run, so consecutive kills add up and yank-pop follows a yank.
+    let mut previous_command: Option<Command> = None;
+    // Whether the mark is set, making cursor movement extend the selection.
+    let mut mark_active = false;
+
+    let mut vim = settings::VIM_MODE.then(Vim::new);
+
+    let mut macros = Macros::load();
+    // The macro being replayed, and the document and undo checkpoint it started at.
+    let mut replay: Option<Replay> = None;
+    let mut replay_checkpoint: Option<(usize, usize)> = None;
+    let mut macro_list: Option<Rect> = None;
+
+    // Words proposed for the word being typed.
+    let mut completion: Option<Completion> = None;
+    let mut other_words = OtherWords::new();
+
+    let mut command_palette = CommandPalette::new();
+    let mut palette_open = false;
+    let mut palette_list: Option<Rect> = None;
+
+    let mut layout = Layout::new(window_size, status_bar_height(&font), status_bar_height(&font), None, None, None);
+    let mut panes = Panes::new();
+    let mut pane_layouts = vec![PaneLayout::new(layout.editor, gutter_width(&font, 1), (0, 0))];
+    let mut divider: Option<Rect> = None;
+    let mut dragging_divider = false;
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let keyboard = sdl_context.keyboard();
 
-    let mut file_path_for_content = None;
-    let mut has_file_been_saved = false;
-    let mut file_name = String::new();
-    let mut file: Option<File> = None;
 
     let mut cursor_visible = true;
     let mut last_cursor_blink = Instant::now();
 
-    let mut scroll_x: i32 = 0;
-    let mut scroll_y: i32 = 0;
-    let mut max_scroll_x = 0;
-    let mut max_scroll_y = 0;
-    let mut can_scroll_vertically_up = false;
-    let mut can_scroll_vertically_down = false;
-    let mut can_scroll_horizontally = false;
-
-    let mut vertical_scroll_bar = Rect::new(0,0,0,0);
-    let mut horizontal_scroll_bar = Rect::new(0,0,0,0);
-    let mut vertical_handle_height = 0;
-    let mut horizontal_handle_width = 0;
-    let mut dragging_scroll_bar_vertical = false;
-    let mut dragging_scroll_bar_horizontal = false;
+    let mut last_recenter: Option<(Recenter, usize)> = None;
+
+    // Vertical and horizontal scroll bar handles of each pane.
+    let mut scroll_handles: Vec<(Option<Rect>, Option<Rect>)> = vec![(None, None)];
+    // Distance between the mouse and the start of the handle being dragged.
+    let mut vertical_drag_offset: Option<i32> = None;
+    let mut horizontal_drag_offset: Option<i32> = None;
+    let mut mouse_position = Point::new(0, 0);
 
     'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Window { win_event, .. } => match win_event {
-                    WindowEvent::Resized(w, h) => {
-                        window_width = w as u32 - settings::SCROLL_BAR_WIDTH;
-                        window_height = h as u32 - settings::SCROLL_BAR_HEIGHT;
-                        viewport_width = w as u32 - settings::SCROLL_BAR_WIDTH;
-                        viewport_height = h as u32 - settings::SCROLL_BAR_HEIGHT;
-                        viewport = Rect::new(0, 0, viewport_width, viewport_height);
-                        canvas.set_viewport(Some(viewport));
-                    }
-                    _ => {}
-                },
-                Event::MouseButtonDown { x, y, .. } => {
-                    if vertical_scroll_bar.contains_point(Point::new(x, y)) {
-                        dragging_scroll_bar_vertical = true;
-                    } else if horizontal_scroll_bar.contains_point(Point::new(x, y)) {
-                        dragging_scroll_bar_horizontal = true;
-                    } else {
-                        let cursor_index = get_nearest_character_position(&font, &buffer.to_string(), x + scroll_x, y + scroll_y);
-                        buffer.move_cursor(cursor_index);
-                    }
+        let active_before_events = documents.active_index();
+        let explorer_selected_before = file_explorer.selected_index();
+        let cursor_before_events = documents.active().buffer.get_cursor();
+        let length_before_events = documents.active().buffer.length();
+        // Set when text was typed, to propose words for completing it.
+        let mut typed_text = false;
+
+        // A macro being replayed runs a repetition per frame instead of new
+        // events, all of them as a single undo step. Escape stops it.
+        let replay_steps = replay.as_mut().and_then(Replay::next_repetition);
+        if replay_steps.is_none() {
+            replay = None;
+            if let Some((index, checkpoint)) = replay_checkpoint.take() {
+                if documents.active_index() == index {
+                    documents.active_mut().buffer.join_undo_steps(checkpoint);
                 }
-                Event::MouseButtonUp { .. } => {
-                    dragging_scroll_bar_vertical = false;
-                    dragging_scroll_bar_horizontal = false;
+            }
+        } else if replay_checkpoint.is_none() {
+            replay_checkpoint = Some((documents.active_index(), documents.active_mut().buffer.undo_checkpoint()));
+        }
+        let polled: Vec<Event> = event_pump.poll_iter().collect();
+        let events: Vec<(Option<MacroStep>, Event)> = match replay_steps {
+            None => polled.into_iter().map(|event| (None, event)).collect(),
+            Some(steps) => {
+                if polled.iter().any(|event| matches!(event, Event::KeyDown { keycode: Some(Keycode::Escape), .. })) {
+                    replay = None;
                 }
-                Event::MouseMotion { x, y, .. } => {
-                    if dragging_scroll_bar_vertical {
-                        let new_handle_y = y - vertical_scroll_bar.height() as i32 / 2;
-                        let new_scroll_y = (new_handle_y as f32 / (window_height - vertical_handle_height) as f32 * max_scroll_y as f32) as i32;
-                        scroll_y = new_scroll_y.clamp(0, max_scroll_y as i32);
-                    } else if dragging_scroll_bar_horizontal {
-                        let new_handle_x = x - horizontal_scroll_bar.width() as i32 / 2;
-                        let new_scroll_x = (new_handle_x as f32 / (window_width - horizontal_handle_width) as f32 * max_scroll_x as f32) as i32;
-                        scroll_x = new_scroll_x.clamp(0, max_scroll_x as i32);
+                // Input would get in the way of the replay, but quitting and
+                // window changes are handled as usual.
+                let steps = steps
+                    .into_iter()
+                    .filter(|_| replay.is_some())
+                    .map(|step| (Some(step), Event::Unknown { timestamp: 0, type_: 0 }));
+                let others = polled
+                    .into_iter()
+                    .filter(|event| matches!(event, Event::Quit { .. } | Event::Window { .. }))
+                    .map(|event| (None, event));
+                steps.chain(others).collect()
+            }
+        };
+
+        for (step, event) in events {
+            // Wheel events do not say where the mouse is, so keep track of it.
+            if let Event::MouseMotion { x, y, .. } = event {
+                mouse_position = Point::new(x, y);
+            }
+
+            // A command chosen in the palette or replayed from a macro, run below as if its key was pressed.
+            let mut command = match step {
+                Some(MacroStep::Command(command)) => Some(command),
+                _ => None,
+            };
+
+            // The command palette sits on top of everything and takes the keyboard and mouse while open.
+            if palette_open {
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        palette_open = false;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        command = command_palette.selected_command();
+                        palette_open = false;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::Up | Keycode::Down)),
+                        ..
+                    } => {
+                        command_palette.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        command_palette.query.pop();
+                        true
+                    }
+                    Event::KeyDown { .. } => true,
+                    Event::TextInput { text, .. } => {
+                        command_palette.query.push_str(text);
+                        true
+                    }
+                    Event::MouseButtonDown { x, y, .. } => {
+                        // Clicking a command runs it, clicking outside the palette closes it.
+                        match palette_list.filter(|list| list.contains_point(Point::new(*x, *y))) {
+                            Some(list) => {
+                                if let Some(index) = get_list_item_at(&font, list, *y, command_palette.scroll) {
+                                    command_palette.select(index);
+                                    if index == command_palette.selected_index() {
+                                        command = command_palette.selected_command();
+                                        palette_open = false;
+                                    }
+                                }
+                            }
+                            None => palette_open = false,
+                        }
+                        true
                     }
+                    _ => false,
+                };
+                if handled && command.is_none() {
+                    continue;
                 }
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                Event::KeyDown {
-                    keycode: Some(Keycode::Return),
-                    ..
-                } => {
-                    if buffer.length() > 0 {
-                        buffer.insert('\n');
+            }
+
+            // The macro prompt asks for a count or a name, and takes the keyboard and mouse while open.
+            if let Some((prompt, _)) = macros.prompt {
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        macros.prompt = None;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        replay = macros.confirm_prompt();
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::Up | Keycode::Down)),
+                        ..
+                    } => {
+                        macros.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        macros.edit_prompt(|text| {
+                            text.pop();
+                        });
+                        true
+                    }
+                    Event::KeyDown { .. } => true,
+                    Event::TextInput { text, .. } => {
+                        macros.edit_prompt(|prompt_text| prompt_text.push_str(text));
+                        true
+                    }
+                    Event::MouseButtonDown { x, y, .. } => {
+                        // Clicking a saved macro plays it, clicking outside the prompt closes it.
+                        match macro_list.filter(|list| list.contains_point(Point::new(*x, *y))) {
+                            Some(list) => {
+                                let index = get_list_item_at(&font, list, *y, macros.scroll);
+                                if let Some(index) = index.filter(|_| prompt == MacroPrompt::Play) {
+                                    macros.select(index);
+                                    if index == macros.selected_index() {
+                                        replay = macros.confirm_prompt();
+                                    }
+                                }
+                            }
+                            None => macros.prompt = None,
+                        }
+                        true
                     }
-                    has_file_been_saved = false;
+                    _ => false,
+                };
+                if handled {
+                    continue;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Backspace),
-                    ..
-                } => {
-                    buffer.remove();
-                    has_file_been_saved = false;
+            }
+
+            // The file finder sits on top of everything and takes the keyboard and mouse while open.
+            if file_finder_open {
+                let mut open_selected = false;
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        file_finder.cancel();
+                        file_finder_open = false;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        open_selected = true;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::Up | Keycode::Down)),
+                        ..
+                    } => {
+                        file_finder.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        file_finder.query.pop();
+                        true
+                    }
+                    Event::KeyDown { .. } => true,
+                    Event::TextInput { text, .. } => {
+                        file_finder.query.push_str(text);
+                        true
+                    }
+                    Event::MouseButtonDown { x, y, .. } => {
+                        // Clicking a file opens it, clicking outside the finder closes it.
+                        match file_finder_list.filter(|list| list.contains_point(Point::new(*x, *y))) {
+                            Some(list) => {
+                                if let Some(index) = get_list_item_at(&font, list, *y, file_finder.scroll) {
+                                    file_finder.select(index);
+                                    open_selected = index == file_finder.selected_index();
+                                }
+                            }
+                            None => {
+                                file_finder.cancel();
+                                file_finder_open = false;
+                            }
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if open_selected {
+                    if let Some(path) = file_finder.selected_path() {
+                        if documents.open(&path) {
+                            block_selection = None;
+                            explorer_focused = false;
+                        }
+                    }
+                    file_finder.cancel();
+                    file_finder_open = false;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Delete),
-                    ..
-                } => {
-                    buffer.remove();
+                if handled {
+                    continue;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left),
-                    ..
-                } => {
-                    if buffer.get_cursor() != 0 {
-                        buffer.move_cursor(buffer.get_cursor() - 1);
+            }
+
+            // The file explorer handles clicks in the sidebar, and the keyboard while it has focus.
+            if let Some(sidebar) = layout.sidebar.filter(|_| command.is_none()) {
+                let ctrl = keyboard.mod_state().intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                let mut open_path: Option<PathBuf> = None;
+                let handled = match &event {
+                    Event::MouseButtonDown { x, y, .. } => {
+                        explorer_focused = sidebar.contains_point(Point::new(*x, *y));
+                        let clicked = get_list_item_at(&font, sidebar, *y, file_explorer.scroll)
+                            .filter(|&index| index < file_explorer.entry_count());
+                        if let (true, Some(index)) = (explorer_focused, clicked) {
+                            file_explorer.select(index);
+                            open_path = file_explorer.activate(index);
+                        }
+                        explorer_focused
+                    }
+                    Event::MouseWheel { y, direction, .. } if sidebar.contains_point(mouse_position) => {
+                        let y = if *direction == MouseWheelDirection::Flipped { -y } else { *y };
+                        let lines = (y * settings::SCROLL_WHEEL_LINES as i32) as isize;
+                        file_explorer.scroll = (file_explorer.scroll as isize - lines).max(0) as usize;
+                        true
+                    }
+                    _ if !explorer_focused => false,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        // Escape cancels a prompt first, then hands the keyboard back to the editor.
+                        if file_explorer.prompt.take().is_none() {
+                            explorer_focused = false;
+                        }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        if file_explorer.prompt.is_some() {
+                            match file_explorer.confirm_prompt() {
+                                Some(ExplorerChange::Created(path)) if path.is_file() => open_path = Some(path),
+                                Some(ExplorerChange::Renamed(from, to)) => documents.rename_path(&from, &to),
+                                _ => {}
+                            }
+                        } else {
+                            open_path = file_explorer.activate(file_explorer.selected_index());
+                        }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } if file_explorer.prompt.is_some() => {
+                        if let Some((_, name)) = file_explorer.prompt.as_mut() {
+                            name.pop();
+                        }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    } if file_explorer.prompt.is_none() => match keycode {
+                        Keycode::Up | Keycode::Down => {
+                            file_explorer.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                            true
+                        }
+                        Keycode::Left => {
+                            file_explorer.collapse_selected();
+                            true
+                        }
+                        Keycode::Right => {
+                            file_explorer.expand_selected();
+                            true
+                        }
+                        Keycode::F2 => {
+                            file_explorer.start_prompt(ExplorerAction::Rename);
+                            true
+                        }
+                        Keycode::Delete => {
+                            if file_explorer.selected_path().is_some_and(confirm_delete) {
+                                file_explorer.delete_selected();
+                            }
+                            true
+                        }
+                        // Ctrl+N creates a file and Ctrl+Shift+N a folder in the selected folder.
+                        Keycode::N if ctrl => {
+                            file_explorer.start_prompt(if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                                ExplorerAction::NewFolder
+                            } else {
+                                ExplorerAction::NewFile
+                            });
+                            true
+                        }
+                        // Other shortcuts such as Ctrl+B or Ctrl+S still reach the editor.
+                        _ => !ctrl,
+                    },
+                    Event::KeyDown { .. } => !ctrl,
+                    Event::TextInput { text, .. } => {
+                        if let Some((_, name)) = file_explorer.prompt.as_mut() {
+                            name.push_str(text);
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if let Some(path) = open_path {
+                    // Opening a file hands the keyboard to it.
+                    if documents.open(&path) {
+                        block_selection = None;
+                        explorer_focused = false;
                     }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
-                    ..
-                } => {
-                    buffer.move_cursor(buffer.get_cursor() + 1);
+                if handled {
+                    continue;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    ..
-                } => {
-                    let (row, col) = buffer.get_cursor_position();
-                    if row > 0 {
-                        // Move the cursor up a line
-                        let target_row = row - 1;
-                        let mut new_cursor = 0;
-                        let mut current_row = 0;
-                        let mut current_col = 0;
-
-                        for (i, c) in buffer.to_string().chars().enumerate() {
-                            if current_row == target_row && (current_col == col || c == '\n') {
-                                new_cursor = i;
-                                break;
+            }
+
+            // The Find in Files panel takes the keyboard while it is open.
+            if project_search_open && command.is_none() {
+                let alt = keyboard.mod_state().intersects(Mod::LALTMOD | Mod::RALTMOD);
+                let mut open_selected = false;
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        project_search.cancel();
+                        project_search_open = false;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        ..
+                    } => {
+                        // Enter searches for a changed query, otherwise opens the selected result.
+                        if project_search.is_up_to_date() {
+                            open_selected = true;
+                        } else {
+                            project_search.start(&project_root);
+                            project_search_scroll = 0;
+                        }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::Up | Keycode::Down)),
+                        ..
+                    } => {
+                        project_search.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        project_search.query.pop();
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::C | Keycode::R)),
+                        ..
+                    } if alt => {
+                        if *keycode == Keycode::C {
+                            project_search.case_sensitive = !project_search.case_sensitive;
+                        } else {
+                            project_search.regex = !project_search.regex;
+                        }
+                        project_search.start(&project_root);
+                        project_search_scroll = 0;
+                        true
+                    }
+                    Event::MouseButtonDown { x, y, .. } => {
+                        let point = Point::new(*x, *y);
+                        match layout.bottom_panel.filter(|panel| panel.contains_point(point)) {
+                            Some(panel) => {
+                                let lines = project_search.lines(&project_root);
+                                let clicked = get_list_item_at(&font, panel, *y, project_search_scroll)
+                                    .and_then(|line| lines.get(line))
+                                    .and_then(|(_, index)| *index);
+                                if let Some(index) = clicked {
+                                    project_search.select(index);
+                                    open_selected = true;
+                                }
+                                true
                             }
-                            if c == '\n' {
-                                current_row += 1;
-                                current_col = 0;
-                            } else {
-                                current_col += 1;
+                            None => false,
+                        }
+                    }
+                    Event::MouseWheel { y, direction, .. } => {
+                        match layout.bottom_panel.filter(|panel| panel.contains_point(mouse_position)) {
+                            Some(_) => {
+                                let y = if *direction == MouseWheelDirection::Flipped { -y } else { *y };
+                                let lines = (y * settings::SCROLL_WHEEL_LINES as i32) as isize;
+                                project_search_scroll = (project_search_scroll as isize - lines).max(0) as usize;
+                                true
                             }
+                            None => false,
+                        }
+                    }
+                    Event::TextInput { text, .. } => {
+                        if !alt {
+                            project_search.query.push_str(text);
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if open_selected {
+                    if let Some((path, line_match)) = project_search.selected() {
+                        let (row, column) = (line_match.row, line_match.column);
+                        if documents.open(path) {
+                            block_selection = None;
+                            let document = documents.active_mut();
+                            let index = document.buffer.get_index_at_position(row, column);
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(index));
                         }
+                    }
+                }
+                if handled {
+                    continue;
+                }
+            }
+
+            let document = documents.active_mut();
+            // Layout of the focused pane as of the last frame. The panes may have
+            // been split or joined since.
+            let focused_pane = panes.focused().min(pane_layouts.len() - 1);
+            let mut pane = pane_layouts[focused_pane];
+            let (mut vertical_handle, mut horizontal_handle) = scroll_handles[focused_pane];
 
-                        buffer.move_cursor(new_cursor);
+            // Text and Vim keys of a macro being replayed go where they went when recorded.
+            match &step {
+                Some(MacroStep::Text(text)) => {
+                    if !vim.as_mut().is_some_and(|vim| vim.handle_text(text, document, &mut clipboard)) {
+                        type_text(document, &mut block_selection, text);
                     }
+                    continue;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => {
-                    let (row, col) = buffer.get_cursor_position();
-                    let total_rows = buffer.to_string().lines().count();
-                    if row < total_rows - 1 {
-                        // Move the cursor down a line
-                        let target_row = row + 1;
-                        let mut new_cursor = 0;
-                        let mut current_row = 0;
-                        let mut current_col = 0;
-
-                        for (i, c) in buffer.to_string().chars().enumerate() {
-                            if current_row == target_row {
-                                if current_col == col || c == '\n' || c == constants::EOF_CHAR {
-                                    new_cursor = i;
-                                    break;
-                                }
-                            }
-                            if c == '\n' {
-                                current_row += 1;
-                                current_col = 0;
-                            } else {
-                                current_col += 1;
+                Some(MacroStep::Vim(key)) => {
+                    if let Some(vim) = vim.as_mut() {
+                        vim.handle_key(*key, document, &mut clipboard);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            // The find bar takes the keyboard while it is open.
+            if find_open && command.is_none() {
+                let alt = keyboard.mod_state().intersects(Mod::LALTMOD | Mod::RALTMOD);
+                let mut query_changed = false;
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        find_open = false;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Tab),
+                        ..
+                    } if find.replacing => {
+                        find.focus = match find.focus {
+                            FindField::Query => FindField::Replacement,
+                            FindField::Replacement => FindField::Query,
+                        };
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        keymod,
+                        ..
+                    } if find.replacing && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) && alt => {
+                        // Ctrl+Alt+Enter replaces every match as one undo step.
+                        if find.replace_all(&mut document.buffer) > 0 {
+                            let cursor = document.buffer.get_cursor();
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(cursor));
+                            document.has_been_saved = false;
+                        }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return),
+                        keymod,
+                        ..
+                    } => {
+                        let found = if find.focus == FindField::Replacement {
+                            if find.current().is_some() {
+                                document.has_been_saved = false;
                             }
+                            find.replace_current(&mut document.buffer)
+                        } else if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            find.previous(document.buffer.get_cursor())
+                        } else {
+                            find.next(document.buffer.get_cursor())
+                        };
+                        let cursor = document.buffer.get_cursor();
+                        match found {
+                            Some((start, end)) => document.selections.set_single(&mut document.buffer, Selection::range(start, end)),
+                            None => document.selections.set_single(&mut document.buffer, Selection::cursor(cursor)),
                         }
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => {
+                        find.focused_field().pop();
+                        query_changed = find.focus == FindField::Query;
+                        true
+                    }
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::C | Keycode::W | Keycode::R | Keycode::L)),
+                        ..
+                    } if alt => {
+                        match keycode {
+                            Keycode::C => find.case_sensitive = !find.case_sensitive,
+                            Keycode::W => find.whole_word = !find.whole_word,
+                            Keycode::R => find.regex = !find.regex,
+                            // Alt+L limits the search to the current selection.
+                            _ => match find.scope() {
+                                Some(_) => find.set_scope(None, &document.buffer),
+                                None => {
+                                    let primary = document.selections.primary();
+                                    find.set_scope(Some((primary.start(), primary.end())), &document.buffer);
+                                }
+                            },
+                        }
+                        query_changed = true;
+                        true
+                    }
+                    Event::TextInput { text, .. } => {
+                        if !alt {
+                            find.focused_field().push_str(text);
+                            query_changed = find.focus == FindField::Query;
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if query_changed {
+                    // Search as you type, jumping to the first match after where the search started.
+                    find.search(&document.buffer);
+                    match find.select_from(find_origin) {
+                        Some((start, end)) => document.selections.set_single(&mut document.buffer, Selection::range(start, end)),
+                        None => document.selections.set_single(&mut document.buffer, Selection::cursor(find_origin)),
+                    }
+                }
+                if handled {
+                    continue;
+                }
+            }
 
-                        buffer.move_cursor(new_cursor);
+            // The completion popup takes the keys for picking a word while it is open.
+            if let (Some(open), None) = (completion.as_mut(), command) {
+                let plain = |keymod: &Mod| {
+                    !keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LALTMOD | Mod::RALTMOD | Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+                };
+                match &event {
+                    Event::KeyDown {
+                        keycode: Some(keycode @ (Keycode::Up | Keycode::Down)),
+                        keymod,
+                        ..
+                    } if plain(keymod) => {
+                        open.select_by(if *keycode == Keycode::Up { -1 } else { 1 });
+                        continue;
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Tab | Keycode::Return),
+                        keymod,
+                        ..
+                    } if plain(keymod) => {
+                        let remainder = open.remainder().to_string();
+                        completion = None;
+                        swallow_text_input = true;
+                        document.selections.insert_text(&mut document.buffer, &remainder);
+                        document.has_been_saved = false;
+                        if let Some(vim) = vim.as_mut() {
+                            vim.record_text(&remainder);
+                        }
+                        macros.record(MacroStep::Text(remainder));
+                        continue;
                     }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => {
+                        completion = None;
+                        continue;
+                    }
+                    _ => {}
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Tab),
-                    ..
-                } => {
-                    for _ in 0..settings::tab_width {
-                        buffer.insert(' ');
+            }
+
+            // Vim emulation takes the keys it understands, and typed text outside insert mode.
+            if let Some(vim) = vim.as_mut().filter(|_| command.is_none() && keymap.pending().is_none()) {
+                let handled = match &event {
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    } => match VimKey::from_keydown(*keycode, *keymod) {
+                        Some(key) if vim.handle_key(key, document, &mut clipboard) => {
+                            macros.record(MacroStep::Vim(key));
+                            true
+                        }
+                        _ => false,
+                    },
+                    Event::TextInput { text, .. } if !swallow_text_input => {
+                        let handled = vim.handle_text(text, document, &mut clipboard);
+                        if handled {
+                            macros.record(MacroStep::Text(text.clone()));
+                        }
+                        handled
                     }
-                    has_file_been_saved = false;
+                    _ => false,
+                };
+                if handled {
+                    block_selection = None;
+                    continue;
                 }
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    keymod,
-                    ..
-                } => {
-                    if keycode == Keycode::O && keymod.contains(sdl2::keyboard::Mod::LCTRLMOD) {
-                        file_path_for_content = FileDialog::new()
+            }
+
+            // Keys run the command bound to them.
+            let command = match (command, &event) {
+                (
+                    None,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    },
+                ) => match keymap.handle(*keycode, *keymod) {
+                    KeyResult::Command(command) => {
+                        swallow_text_input = true;
+                        Some(command)
+                    }
+                    KeyResult::Pending | KeyResult::ChordCancelled => {
+                        swallow_text_input = true;
+                        continue;
+                    }
+                    KeyResult::Unbound => {
+                        swallow_text_input = false;
+                        None
+                    }
+                },
+                (command, _) => command,
+            };
+            if let Some(command) = command {
+                match command {
+                    Command::OpenFile => {
+                        let file_path_for_content = FileDialog::new()
                             .set_location("~/Desktop")
                             .add_filter("Text Documents", &["txt"])
                             .add_filter("All Documents", &["*"])
@@ -253,101 +926,673 @@ pub fn main() {
                             .expect("Failed to get file path.");
 
                         if let Some(path) = file_path_for_content {
-                            let mut file = match File::open(&path) {
-                                Ok(file) => file,
-                                Err(e) => {
-                                    eprintln!("Unable to open file: {:?}", e);
-                                    continue;
-                                }
-                            };
-                            let mut contents = String::new();
-                            if let Err(e) = file.read_to_string(&mut contents) {
-                                eprintln!("Unable to read file: {:?}", e);
-                                continue;
-                            }
-
-                            file_name = path
-                                .file_name()
-                                .and_then(|os_str| os_str.to_str())
-                                .map(|str| str.to_string()).unwrap();
-
-
-                            // Clear the buffer and insert the file's contents
-                            buffer.clear();
-                            for c in contents.chars() {
-                                buffer.insert(c);
+                            // Open the file in its own tab
+                            if documents.open(&path) {
+                                block_selection = None;
                             }
-                            has_file_been_saved = true;
                         }
                     }
-                    else if keycode == Keycode::S && keymod.contains(sdl2::keyboard::Mod::LCTRLMOD) {
-                        if file.is_some() {
+                    Command::Save => {
+                        if let Some(path) = document.path.clone() {
                             // Save the buffer content to the existing file
-                            file.as_mut().unwrap().write(buffer.to_string().as_bytes())
-                                .expect("Could not save to file");
-                            has_file_been_saved = true;
+                            document.save(&path);
                         } else {
                             // No file is opened, show the file dialog to create a new file
                             let current_dir = env::current_dir().unwrap();
-                            file_path_for_content = FileDialog::new()
+                            let file_path_for_content = FileDialog::new()
                                 .add_filter("Text files", &["txt", "md"])
                                 .add_filter("All files", &["*"])
                                 .set_location(current_dir.as_path())
                                 .set_filename("new.txt")
                                 .show_save_single_file()
                                 .expect("Failed to open dialogue");
-                    
+
                             if let Some(path) = file_path_for_content {
-                                file = match File::create(&path) {
-                                    Ok(file) => Some(file),
-                                    Err(e) => {
-                                        eprintln!("Unable to open file: {:?}", e);
-                                        continue;
+                                document.save(&path);
+                            }
+                        }
+                    }
+                    Command::NewTab => {
+                        block_selection = None;
+                        documents.add(Document::new());
+                    }
+                    Command::CloseTab => {
+                        block_selection = None;
+                        if confirm_close(documents.active()) {
+                            documents.close(documents.active_index());
+                        }
+                    }
+                    Command::ReopenClosedTab => {
+                        block_selection = None;
+                        documents.reopen_closed();
+                    }
+                    Command::NextTab | Command::PreviousTab => {
+                        block_selection = None;
+                        documents.cycle(if command == Command::PreviousTab { -1 } else { 1 });
+                    }
+                    Command::GoToFile => {
+                        palette_open = false;
+                        file_finder.start(&project_root);
+                        file_finder_open = true;
+                    }
+                    Command::ShowCommandPalette => {
+                        macros.prompt = None;
+                        file_finder.cancel();
+                        file_finder_open = false;
+                        command_palette.reset();
+                        palette_open = true;
+                    }
+                    Command::Quit => break 'running,
+                    Command::Move(motion, extend) => {
+                        block_selection = None;
+                        let extend = extend || mark_active;
+                        let selections = &mut document.selections;
+                        let buffer = &mut document.buffer;
+                        match motion {
+                            Motion::Left => selections.move_each(buffer, extend, |_, index| index.saturating_sub(1)),
+                            Motion::Right => selections.move_each(buffer, extend, |_, index| index + 1),
+                            Motion::Up => selections.move_each_vertically(buffer, extend, -1),
+                            Motion::Down => selections.move_each_vertically(buffer, extend, 1),
+                            Motion::WordLeft => selections.move_each(buffer, extend, motions::word_left),
+                            Motion::WordRight => selections.move_each(buffer, extend, motions::word_right),
+                            Motion::ParagraphUp => selections.move_each(buffer, extend, motions::paragraph_up),
+                            Motion::ParagraphDown => selections.move_each(buffer, extend, motions::paragraph_down),
+                            Motion::LineStart => selections.move_each(buffer, extend, motions::smart_home),
+                            Motion::LineEnd => selections.move_each(buffer, extend, motions::line_end),
+                            Motion::DocumentStart => selections.move_each(buffer, extend, |_, _| 0),
+                            Motion::DocumentEnd => selections.move_each(buffer, extend, |buffer, _| buffer.length() - 1),
+                            Motion::PageUp | Motion::PageDown => {
+                                // Move the view and the cursor by the same number of lines so the
+                                // cursor keeps its place on screen.
+                                let line_height = font.height();
+                                let page_lines = (pane.text_area.height() as i32 / line_height).max(1);
+                                let (row, _) = buffer.get_cursor_position();
+                                let target_row = if motion == Motion::PageUp {
+                                    row.saturating_sub(page_lines as usize)
+                                } else {
+                                    (row + page_lines as usize).min(buffer.get_line_count() - 1)
+                                };
+                                let lines_moved = target_row as i32 - row as i32;
+
+                                document.scroll.scroll_by((0, lines_moved * line_height), pane.max_scroll);
+                                selections.move_each_vertically(buffer, extend, lines_moved as isize);
+                            }
+                        }
+                    }
+                    Command::ExtendBlock(motion) => {
+                        // Alt+Shift+arrows grow a block selection from the cursor.
+                        let block = block_selection.get_or_insert_with(|| BlockSelection::at(document.buffer.get_cursor_position()));
+                        let (row, col) = block.head;
+                        block.head = match motion {
+                            Motion::Left => (row, col.saturating_sub(1)),
+                            Motion::Right => (row, col + 1),
+                            Motion::Up => (row.saturating_sub(1), col),
+                            _ => ((row + 1).min(document.buffer.get_line_count() - 1), col),
+                        };
+                        // Keep the cursor at the head so the view follows it.
+                        let head_index = document.buffer.get_index_at_position(block.head.0, block.head.1);
+                        document.selections.set_single(&mut document.buffer, Selection::cursor(head_index));
+                    }
+                    Command::AddCursorAbove | Command::AddCursorBelow => {
+                        block_selection = None;
+                        document.selections.add_cursor_vertically(&mut document.buffer, command == Command::AddCursorAbove);
+                    }
+                    Command::AddNextOccurrence => {
+                        document.selections.add_next_occurrence(&mut document.buffer);
+                    }
+                    Command::KillLine | Command::KillRegion | Command::CopyRegion => {
+                        block_selection = None;
+                        let buffer = &mut document.buffer;
+                        // Each cursor kills its own range, and the texts go into a single entry, one per line.
+                        let mut previous_end = 0;
+                        let ranges: Vec<(usize, usize)> = document
+                            .selections
+                            .iter()
+                            .map(|selection| {
+                                let (start, end) = if command == Command::KillLine {
+                                    // Kills the rest of the line, or its line break if only blanks are left.
+                                    let cursor = selection.head;
+                                    let line_end = motions::line_end(buffer, cursor);
+                                    if (cursor..line_end).all(|index| buffer.get_char(index).is_whitespace()) {
+                                        (cursor, (line_end + 1).min(buffer.length() - 1))
+                                    } else {
+                                        (cursor, line_end)
                                     }
+                                } else {
+                                    (selection.start(), selection.end())
                                 };
-                    
-                                file_name = path
-                                    .file_name()
-                                    .and_then(|os_str| os_str.to_str())
-                                    .map(|str| str.to_string()).unwrap();
-                    
-                                file.as_mut().unwrap().write_all(buffer.to_string().as_bytes())
-                                    .expect("Could not save to file");
-                    
-                                has_file_been_saved = true;
+                                // Cursors on the same line kill the rest of it only once.
+                                let start = start.max(previous_end);
+                                previous_end = end.max(start);
+                                (start, previous_end)
+                            })
+                            .collect();
+                        if ranges.iter().any(|(start, end)| start < end) {
+                            let texts: Vec<String> = ranges
+                                .iter()
+                                .map(|&(start, end)| (start..end).map(|index| buffer.get_char(index)).collect())
+                                .collect();
+                            let appending = matches!(previous_command, Some(Command::KillLine | Command::KillRegion));
+                            clipboard.set_text(kill_ring.kill(&texts.join("\n"), appending));
+                            if command != Command::CopyRegion {
+                                let edits = ranges.into_iter().map(|(start, end)| (start, end, String::new(), 0)).collect();
+                                document.selections.edit_all(buffer, edits);
+                                document.has_been_saved = false;
+                            }
+                        }
+                        if command == Command::CopyRegion {
+                            document.selections.map_each(&mut document.buffer, |selection| Selection::cursor(selection.head));
+                        }
+                    }
+                    Command::Yank => {
+                        if let Some(text) = kill_ring.yank(clipboard.text()) {
+                            block_selection = None;
+                            document.selections.insert_text(&mut document.buffer, &text);
+                            document.has_been_saved = false;
+                        }
+                    }
+                    Command::YankPop => {
+                        // Only right after a yank, replacing the text it inserted.
+                        let follows_yank = matches!(previous_command, Some(Command::Yank | Command::YankPop));
+                        let popped = (follows_yank && document.selections.count() == 1).then(|| kill_ring.yank_pop()).flatten();
+                        if let Some((length, text)) = popped {
+                            let buffer = &mut document.buffer;
+                            let cursor = buffer.get_cursor();
+                            let start = cursor.saturating_sub(length);
+                            buffer.begin_undo_group();
+                            buffer.delete_range(start, cursor);
+                            buffer.insert_str(start, &text);
+                            buffer.end_undo_group();
+                            let cursor = start + text.chars().count();
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(cursor));
+                            document.has_been_saved = false;
+                        }
+                    }
+                    Command::SetMark => {
+                        // Setting the mark again without moving turns it off.
+                        block_selection = None;
+                        let primary = document.selections.primary();
+                        mark_active = !(mark_active && primary.is_empty());
+                        document.selections.set_single(&mut document.buffer, Selection::cursor(primary.head));
+                    }
+                    Command::ClearSelection => {
+                        block_selection = None;
+                        document.selections.collapse(&mut document.buffer);
+                    }
+                    Command::ClearSelectionOrQuit => {
+                        // Escape first drops extra cursors and the selection before quitting.
+                        if block_selection.take().is_none() && !document.selections.collapse(&mut document.buffer) {
+                            break 'running;
+                        }
+                    }
+                    Command::Newline => {
+                        document.buffer.begin_undo_group();
+                        if let Some(block) = block_selection.take() {
+                            let positions = block.replace(&mut document.buffer, &[""]);
+                            document.selections.set_cursors(&mut document.buffer, &positions);
+                        }
+                        document.newline();
+                        document.buffer.end_undo_group();
+                    }
+                    Command::DeleteBackward => {
+                        if let Some(block) = block_selection.take() {
+                            let positions = block.delete_backward(&mut document.buffer);
+                            document.selections.set_cursors(&mut document.buffer, &positions);
+                            document.has_been_saved = false;
+                        } else {
+                            document.delete_backward();
+                        }
+                    }
+                    Command::DeleteForward => {
+                        if let Some(block) = block_selection.take() {
+                            let positions = block.delete_forward(&mut document.buffer);
+                            document.selections.set_cursors(&mut document.buffer, &positions);
+                            document.has_been_saved = false;
+                        } else {
+                            document.delete_forward();
+                        }
+                    }
+                    Command::InsertTab => {
+                        if let Some(block) = block_selection.take() {
+                            let unit = document.indent_unit();
+                            let positions = block.replace(&mut document.buffer, &[&unit]);
+                            document.selections.set_cursors(&mut document.buffer, &positions);
+                            document.has_been_saved = false;
+                        } else {
+                            document.insert_tab();
+                        }
+                    }
+                    Command::Outdent => {
+                        block_selection = None;
+                        let indent = document.indent;
+                        indentation::shift_lines(&mut document.buffer, &mut document.selections, indent, true);
+                        document.has_been_saved = false;
+                    }
+                    Command::JumpToMatchingBracket => {
+                        block_selection = None;
+                        document.selections.move_each(&mut document.buffer, false, |buffer, index| {
+                            brackets::pair_at(buffer, index).map_or(index, |(_, matching)| matching)
+                        });
+                    }
+                    Command::DuplicateLines
+                    | Command::DeleteLines
+                    | Command::MoveLinesUp
+                    | Command::MoveLinesDown
+                    | Command::JoinLines
+                    | Command::SortLines(_)
+                    | Command::ReverseLines
+                    | Command::ChangeCase(_) => {
+                        block_selection = None;
+                        let buffer = &mut document.buffer;
+                        let selections = &mut document.selections;
+                        match command {
+                            Command::DuplicateLines => line_operations::duplicate(buffer, selections),
+                            Command::DeleteLines => line_operations::delete(buffer, selections),
+                            Command::MoveLinesUp => line_operations::move_lines(buffer, selections, true),
+                            Command::MoveLinesDown => line_operations::move_lines(buffer, selections, false),
+                            Command::JoinLines => line_operations::join(buffer, selections),
+                            Command::SortLines(options) => line_operations::sort(buffer, selections, options),
+                            Command::ReverseLines => line_operations::reverse(buffer, selections),
+                            Command::ChangeCase(change) => line_operations::set_case(buffer, selections, change),
+                            _ => {}
+                        }
+                        document.has_been_saved = false;
+                    }
+                    Command::ToggleLineComment | Command::ToggleBlockComment => {
+                        // Languages without line comments comment out lines with block comments.
+                        let Some(language) = document.language() else {
+                            continue;
+                        };
+                        block_selection = None;
+                        let buffer = &mut document.buffer;
+                        let selections = &mut document.selections;
+                        match (command, language.line_comment, language.block_comment) {
+                            (Command::ToggleLineComment, Some(token), _) => comments::toggle_line_comments(buffer, selections, token),
+                            (_, _, Some(markers)) => comments::toggle_block_comments(buffer, selections, markers),
+                            _ => continue,
+                        }
+                        document.has_been_saved = false;
+                    }
+                    Command::ToggleIndentStyle => {
+                        document.indent = match document.indent {
+                            IndentStyle::Tabs => IndentStyle::Spaces(settings::tab_width),
+                            IndentStyle::Spaces(_) => IndentStyle::Tabs,
+                        };
+                    }
+                    Command::Undo | Command::Redo => {
+                        let positions = if command == Command::Undo {
+                            document.buffer.undo()
+                        } else {
+                            document.buffer.redo()
+                        };
+                        if let Some(positions) = positions {
+                            document.selections.set_cursors(&mut document.buffer, &positions);
+                            document.has_been_saved = false;
+                        }
+                    }
+                    Command::Copy => {
+                        clipboard.copy(&document.buffer, &document.selections, block_selection.as_ref());
+                    }
+                    Command::Cut => {
+                        clipboard.cut(&mut document.buffer, &mut document.selections, &mut block_selection);
+                        document.has_been_saved = false;
+                    }
+                    Command::Paste => {
+                        clipboard.paste(&mut document.buffer, &mut document.selections, &mut block_selection);
+                        document.has_been_saved = false;
+                    }
+                    Command::Find | Command::Replace => {
+                        // Replace also shows the replacement field.
+                        let primary = document.selections.primary();
+                        let selected = &document.selections.primary_text(&document.buffer);
+                        find.replacing = command == Command::Replace;
+                        find.focus = FindField::Query;
+                        find.set_scope(None, &document.buffer);
+                        if selected.contains('\n') {
+                            // Replacing in a multi-line selection limits the search to it.
+                            if find.replacing {
+                                find.set_scope(Some((primary.start(), primary.end())), &document.buffer);
                             }
+                        } else if !primary.is_empty() {
+                            // Search for the selected text if there is some on a single line.
+                            find.query = selected.clone();
+                        }
+                        block_selection = None;
+                        project_search.cancel();
+                        project_search_open = false;
+                        find_open = true;
+                        find_origin = primary.start();
+                        find.search(&document.buffer);
+                        if let Some((start, end)) = find.select_from(find_origin) {
+                            document.selections.set_single(&mut document.buffer, Selection::range(start, end));
+                        }
+                    }
+                    Command::FindNext | Command::FindPrevious => {
+                        block_selection = None;
+                        find.search(&document.buffer);
+                        let found = if command == Command::FindPrevious {
+                            find.previous(document.buffer.get_cursor())
+                        } else {
+                            find.next(document.buffer.get_cursor())
+                        };
+                        if let Some((start, end)) = found {
+                            document.selections.set_single(&mut document.buffer, Selection::range(start, end));
                         }
                     }
+                    Command::FindInFiles => {
+                        // Search for the selected text if there is some on a single line.
+                        let selected = &document.selections.primary_text(&document.buffer);
+                        if !selected.is_empty() && !selected.contains('\n') {
+                            project_search.query = selected.clone();
+                        }
+                        find_open = false;
+                        project_search_open = true;
+                    }
+                    Command::Recenter => {
+                        // Recentering again without moving the cursor cycles center -> top -> bottom.
+                        let position = match last_recenter {
+                            Some((position, cursor)) if cursor == document.buffer.get_cursor() => position.next(),
+                            _ => Recenter::Center,
+                        };
+                        let (_, cursor_y) =
+                            get_cursor_position(&font, &document.buffer.to_string(), document.buffer.get_cursor());
+                        let (_, text_height) = get_text_size(&document.buffer.to_string(), &font);
+                        document.scroll.scroll_to((
+                            document.scroll.target().0,
+                            recenter(&font, cursor_y, pane.text_area.height(), text_height, position),
+                        ));
+                        last_recenter = Some((position, document.buffer.get_cursor()));
+                    }
+                    Command::ToggleSidebar => {
+                        explorer_open = !explorer_open;
+                        explorer_focused = false;
+                        if explorer_open {
+                            file_explorer.refresh();
+                        }
+                    }
+                    Command::FocusSidebar => {
+                        // Shows the sidebar and gives it the keyboard.
+                        if !explorer_open {
+                            explorer_open = true;
+                            file_explorer.refresh();
+                        }
+                        explorer_focused = true;
+                    }
+                    Command::SplitSideBySide | Command::SplitStacked => {
+                        let direction = if command == Command::SplitStacked {
+                            SplitDirection::Stacked
+                        } else {
+                            SplitDirection::SideBySide
+                        };
+                        block_selection = None;
+                        panes.toggle_split(direction, document);
+                    }
+                    Command::FocusNextPane => {
+                        let next = (panes.focused() + 1) % panes.count();
+                        if panes.focus(next, document) {
+                            block_selection = None;
+                        }
+                    }
+                    Command::ToggleVimMode => {
+                        vim = match vim {
+                            Some(_) => None,
+                            None => Some(Vim::new()),
+                        };
+                    }
+                    Command::ToggleMacroRecording => macros.toggle_recording(),
+                    Command::ReplayMacro => replay = macros.replay(1),
+                    Command::ReplayMacroTimes => macros.start_prompt(MacroPrompt::ReplayTimes),
+                    Command::SaveMacro => macros.start_prompt(MacroPrompt::Save),
+                    Command::PlayMacro => macros.start_prompt(MacroPrompt::Play),
+                    Command::ToggleEmacsKeybindings => {
+                        keymap = Keymap::load(if keymap.profile() == KeymapProfile::Emacs {
+                            KeymapProfile::Default
+                        } else {
+                            KeymapProfile::Emacs
+                        });
+                    }
+                }
+                // The mark stays active only while the cursor moves.
+                if !matches!(command, Command::Move(..) | Command::SetMark | Command::Recenter) {
+                    mark_active = false;
+                }
+                previous_command = Some(command);
+                // Macros record the commands that edit and move, not the ones that run macros.
+                if !matches!(
+                    command,
+                    Command::ToggleMacroRecording
+                        | Command::ReplayMacro
+                        | Command::ReplayMacroTimes
+                        | Command::SaveMacro
+                        | Command::PlayMacro
+                        | Command::ShowCommandPalette
+                ) {
+                    macros.record(MacroStep::Command(command));
                 }
-                Event::MouseWheel { mut y, .. } => {
-                    if !can_scroll_vertically_up {
-                        if y > 0 {
-                            y = 0
+                continue;
+            }
+
+            match event {
+                Event::Window {
+                    win_event: WindowEvent::Resized(w, h),
+                    ..
+                } => {
+                    window_size = (w as u32, h as u32);
+                }
+                Event::MouseButtonDown { x, y, .. } => {
+                    mark_active = false;
+                    previous_command = None;
+                    let point = Point::new(x, y);
+                    // Clicking into another pane focuses it before the click is handled there.
+                    if let Some(index) = pane_layouts.iter().position(|pane| pane.area.contains_point(point)) {
+                        if panes.focus(index, document) {
+                            block_selection = None;
+                            pane = pane_layouts[index];
+                            (vertical_handle, horizontal_handle) = scroll_handles[index];
                         }
                     }
-                    if !can_scroll_vertically_down {
-                        if y < 0 {
-                            y = 0
+                    if let Some(index) = tabs.iter().position(|(tab, _)| tab.contains_point(point)) {
+                        block_selection = None;
+                        if tabs[index].1.contains_point(point) {
+                            if confirm_close(documents.get(index)) {
+                                documents.close(index);
+                            }
+                        } else {
+                            documents.set_active(index);
+                        }
+                    } else if divider.is_some_and(|divider| divider.contains_point(point)) {
+                        dragging_divider = true;
+                    } else if let Some(handle) = vertical_handle.filter(|handle| handle.contains_point(point)) {
+                        vertical_drag_offset = Some(y - handle.y());
+                    } else if let Some(handle) = horizontal_handle.filter(|handle| handle.contains_point(point)) {
+                        horizontal_drag_offset = Some(x - handle.x());
+                    } else if pane.vertical_scroll_bar.is_some_and(|track| track.contains_point(point)) {
+                        // Clicking the track pages towards the click.
+                        let page = pane.text_area.height() as i32;
+                        let direction = if y < vertical_handle.map_or(0, |handle| handle.y()) { -1 } else { 1 };
+                        document.scroll.scroll_by((0, direction * page), pane.max_scroll);
+                    } else if pane.horizontal_scroll_bar.is_some_and(|track| track.contains_point(point)) {
+                        let page = pane.text_area.width() as i32;
+                        let direction = if x < horizontal_handle.map_or(0, |handle| handle.x()) { -1 } else { 1 };
+                        document.scroll.scroll_by((direction * page, 0), pane.max_scroll);
+                    } else if pane.text_area.contains_point(point) {
+                        let (text_x, text_y) = pane.text_position((x, y), document.scroll.position());
+                        let cursor_index = get_nearest_character_position(&font, &document.buffer.to_string(), text_x, text_y);
+                        let keymod = keyboard.mod_state();
+                        block_selection = None;
+                        if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+                            // Alt+drag selects a block.
+                            let (row, col) = get_nearest_grid_position(&font, text_x, text_y);
+                            let position = (row.min(document.buffer.get_line_count() - 1), col);
+                            block_selection = Some(BlockSelection::at(position));
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(cursor_index));
+                            selecting_with_mouse = true;
+                        } else if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            document.selections.toggle_cursor(&mut document.buffer, cursor_index);
+                        } else if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            document.selections.move_each(&mut document.buffer, true, |_, _| cursor_index);
+                            selecting_with_mouse = true;
+                        } else {
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(cursor_index));
+                            selecting_with_mouse = true;
+                        }
+                    }
+                }
+                Event::MouseButtonUp { .. } => {
+                    vertical_drag_offset = None;
+                    horizontal_drag_offset = None;
+                    selecting_with_mouse = false;
+                    dragging_divider = false;
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    if dragging_divider {
+                        panes.drag_divider(layout.editor, Point::new(x, y));
+                    } else if let (Some(offset), Some(track), Some(handle)) = (vertical_drag_offset, pane.vertical_scroll_bar, vertical_handle) {
+                        let new_handle_y = y - track.y() - offset;
+                        let handle_range = track.height().saturating_sub(handle.height()).max(1);
+                        let new_scroll_y = (new_handle_y as f32 / handle_range as f32 * pane.max_scroll.1 as f32) as i32;
+                        document.scroll.jump_to((document.scroll.target().0, new_scroll_y.clamp(0, pane.max_scroll.1 as i32)));
+                    } else if let (Some(offset), Some(track), Some(handle)) = (horizontal_drag_offset, pane.horizontal_scroll_bar, horizontal_handle) {
+                        let new_handle_x = x - track.x() - offset;
+                        let handle_range = track.width().saturating_sub(handle.width()).max(1);
+                        let new_scroll_x = (new_handle_x as f32 / handle_range as f32 * pane.max_scroll.0 as f32) as i32;
+                        document.scroll.jump_to((new_scroll_x.clamp(0, pane.max_scroll.0 as i32), document.scroll.target().1));
+                    } else if selecting_with_mouse {
+                        let (text_x, text_y) = pane.text_position((x, y), document.scroll.position());
+                        let cursor_index = get_nearest_character_position(&font, &document.buffer.to_string(), text_x, text_y);
+                        if let Some(block) = block_selection.as_mut() {
+                            let (row, col) = get_nearest_grid_position(&font, text_x, text_y);
+                            block.head = (row.min(document.buffer.get_line_count() - 1), col);
+                            document.selections.set_single(&mut document.buffer, Selection::cursor(cursor_index));
+                        } else {
+                            let mut selection = document.selections.primary();
+                            selection.head = cursor_index;
+                            document.selections.set_single(&mut document.buffer, selection);
                         }
                     }
-                    scroll_y += y * -10;
-                    if scroll_y < 0 {
-                        scroll_y = 0;
+                }
+                Event::Quit { .. } => break 'running,
+                Event::MouseWheel { mut x, mut y, direction, .. } => {
+                    if direction == MouseWheelDirection::Flipped {
+                        (x, y) = (-x, -y);
+                    }
+                    // Shift turns the vertical wheel into a horizontal one.
+                    if keyboard.mod_state().intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) && x == 0 {
+                        (x, y) = (-y, 0);
+                    }
+                    let line_height = font.height();
+                    let char_width = font.size_of_char(' ').unwrap().0 as i32;
+                    let delta = (
+                        x * settings::SCROLL_WHEEL_COLUMNS as i32 * char_width,
+                        -y * settings::SCROLL_WHEEL_LINES as i32 * line_height,
+                    );
+                    // The wheel scrolls whichever pane is under the mouse, focused or not.
+                    let hovered = pane_layouts
+                        .iter()
+                        .position(|pane| pane.area.contains_point(mouse_position))
+                        .filter(|&index| index != panes.focused());
+                    match (hovered, panes.other_mut()) {
+                        (Some(index), Some(other)) => {
+                            other.scroll.scroll_by(delta, pane_layouts[index].max_scroll);
+                        }
+                        _ => document.scroll.scroll_by(delta, pane.max_scroll),
                     }
                 }
+                Event::TextInput { .. } if swallow_text_input => swallow_text_input = false,
                 Event::TextInput {
                     timestamp: _,
                     window_id: _,
                     text,
                 } => {
-                    buffer.insert(text.chars().next().unwrap());
-                    has_file_been_saved = false;
+                    mark_active = false;
+                    previous_command = None;
+                    type_text(document, &mut block_selection, &text);
+                    macros.record(MacroStep::Text(text));
+                    typed_text = true;
                 }
                 _ => {}
             }
         }
+        // Words are proposed as they are typed with a single cursor, and the
+        // proposals go away once the cursor is moved or the text changed otherwise.
+        if typed_text && documents.active().selections.count() == 1 && block_selection.is_none() {
+            let others = other_words.update(&documents);
+            let document = documents.active();
+            completion = Completion::at(&document.buffer, document.buffer.get_cursor(), others);
+        } else if typed_text
+            || documents.active_index() != active_before_events
+            || documents.active().buffer.get_cursor() != cursor_before_events
+            || documents.active().buffer.length() != length_before_events
+        {
+            completion = None;
+        }
         // The rest of the game loop goes here...
 
+        let titles: Vec<String> = documents.iter().map(|document| document.title()).collect();
+        let documents_active = documents.active_index();
+        let active_changed = documents_active != active_before_events;
+        let document = documents.active_mut();
+        if active_changed {
+            panes.reset_other(document);
+        }
+        // The find scope follows the edits. Changes made to a document in the
+        // background are dropped when it becomes active.
+        let changes = document.buffer.take_changes();
+        let changes = if active_changed { &[][..] } else { &changes[..] };
+        find.follow_changes(changes);
+
+        // The gutter grows with the number of digits needed for the last line number.
+        let line_count = document.buffer.get_line_count();
+        let (text_width, text_height) = get_text_size(&document.buffer.to_string(), &font);
+        layout = Layout::new(
+            window_size,
+            status_bar_height(&font),
+            status_bar_height(&font),
+            explorer_open.then_some(settings::SIDEBAR_WIDTH),
+            find_open.then(|| status_bar_height(&font) * if find.replacing { 2 } else { 1 }),
+            project_search_open.then(|| status_bar_height(&font) + settings::SEARCH_PANEL_LINES * font.height() as u32),
+        );
+        let (pane_areas, divider_area) = panes.areas(layout.editor);
+        divider = divider_area;
+        pane_layouts = pane_areas
+            .into_iter()
+            .map(|area| PaneLayout::new(area, gutter_width(&font, line_count), (text_width, text_height)))
+            .collect();
+        let focused_layout = pane_layouts[panes.focused()];
+        project_search.poll();
+        if find_open {
+            // Keep the matches up to date with any edits.
+            find.search(&document.buffer);
+        }
+
+        // Scroll the view along with the cursor whenever it moved or the text changed.
+        if active_changed
+            || document.buffer.get_cursor() != cursor_before_events
+            || document.buffer.length() != length_before_events
+        {
+            let (cursor_x, cursor_y) =
+                get_cursor_position(&font, &document.buffer.to_string(), document.buffer.get_cursor());
+            let target = scroll_to_cursor(
+                &font,
+                (cursor_x, cursor_y),
+                (focused_layout.text_area.width(), focused_layout.text_area.height()),
+                (text_width, text_height),
+                document.scroll.target(),
+            );
+            // Switching documents jumps straight to the new view instead of easing there.
+            if active_changed {
+                document.scroll.jump_to(target);
+            } else {
+                document.scroll.scroll_to(target);
+            }
+        }
+        document.scroll.update();
+        if let Some(other) = panes.other_mut() {
+            // The other pane's cursors stay on their text through edits made in the focused pane.
+            other.selections.follow_changes(&document.buffer, changes);
+            other.scroll.update();
+        }
+
         // Keeps track of cursor blinking
         if last_cursor_blink.elapsed() >= Duration::from_millis(constants::CURSOR_BLINK_DURATION) {
             cursor_visible = !cursor_visible;
@@ -357,61 +1602,380 @@ pub fn main() {
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
 
-        if file_name == String::new(){
-            canvas.window_mut().set_title(&format!("{}{}", &"Untitled", if has_file_been_saved {&""} else {&"*"})).unwrap();
-        } else {
-            canvas.window_mut().set_title(&format!("{}{}", &file_name, if has_file_been_saved {&""} else {&"*"})).unwrap();
+        canvas.window_mut().set_title(&document.title()).unwrap();
+        tabs = render_tab_bar(&mut canvas, &font, layout.tab_bar, &titles, documents_active);
+
+        let text = document.buffer.to_string();
+        let match_rows = if find_open { find.match_rows(&document.buffer) } else { Vec::new() };
+        scroll_handles.clear();
+        // Where the focused pane's cursor is on screen, for placing the completion popup.
+        let mut focused_cursor = (0, 0);
+        for (index, pane) in pane_layouts.iter().enumerate() {
+            // Both panes show the same text, each through its own cursors and scroll position.
+            let focused = index == panes.focused();
+            let (selections, scroll) = match panes.other() {
+                Some(other) if !focused => (&other.selections, other.scroll),
+                _ => (&document.selections, document.scroll),
+            };
+            let (scroll_x, scroll_y) = scroll.position();
+
+            canvas.set_viewport(Some(pane.text_area));
+            if find_open {
+                if let Some(scope) = find.scope() {
+                    render_highlights(&mut canvas, &font, &text, &[scope], Color::RGB(45, 45, 55), scroll_x, scroll_y);
+                }
+                render_highlights(&mut canvas, &font, &text, find.matches(), Color::RGB(98, 76, 32), scroll_x, scroll_y);
+            }
+            match &block_selection {
+                Some(block) if focused => {
+                    render_block_selection(&mut canvas, &font, block.rows(), block.columns(), scroll_x, scroll_y);
+                }
+                _ => {
+                    let selection_ranges: Vec<(usize, usize)> =
+                        selections.iter().map(|selection| (selection.start(), selection.end())).collect();
+                    render_selections(&mut canvas, &font, &text, &selection_ranges, scroll_x, scroll_y);
+                }
+            }
+            // The bracket at the cursor and the one matching it.
+            if let Some((bracket, matching)) = brackets::pair_at(&document.buffer, selections.primary().head) {
+                let ranges = [(bracket, bracket + 1), (matching, matching + 1)];
+                render_highlights(&mut canvas, &font, &text, &ranges, Color::RGB(70, 70, 80), scroll_x, scroll_y);
+            }
+            render_text(&mut canvas, &font, &text, scroll_x, scroll_y);
+
+            // A block selection has its cursor at the head corner, which may be in virtual space.
+            let cursor_positions: Vec<(i32, i32)> = match &block_selection {
+                Some(block) if focused => {
+                    let char_width = font.size_of_char(' ').unwrap().0 as i32;
+                    vec![(block.head.1 as i32 * char_width, block.head.0 as i32 * font.height())]
+                }
+                _ => selections
+                    .iter()
+                    .map(|selection| get_cursor_position(&font, &text, selection.head))
+                    .collect(),
+            };
+            for (cursor_x, cursor_y) in cursor_positions {
+                // Only the focused pane's cursors blink.
+                render_cursor(
+                    &mut canvas,
+                    &font,
+                    cursor_x,
+                    cursor_y,
+                    cursor_visible || !focused,
+                    scroll_x,
+                    scroll_y,
+                );
+            }
+            canvas.set_viewport(None);
+
+            let head = selections.primary().head;
+            let (cursor_x, cursor_y) = get_cursor_position(&font, &text, head);
+            let (cursor_row, _) = document.buffer.get_position_of_index(head);
+            if focused {
+                focused_cursor = (pane.text_area.x() + cursor_x - scroll_x, pane.text_area.y() + cursor_y - scroll_y);
+            }
+            canvas.set_clip_rect(Some(pane.gutter));
+            render_gutter(&mut canvas, &font, pane.gutter, line_count, cursor_row, scroll_y);
+            canvas.set_clip_rect(None);
+
+            let mut markers = vec![ScrollBarMarker { y: cursor_y, color: Color::RGB(0, 120, 215) }];
+            markers.extend(match_rows.iter().map(|&row| ScrollBarMarker {
+                y: row as i32 * font.height(),
+                color: Color::RGB(215, 160, 40),
+            }));
+            scroll_handles.push(render_scroll_bars(
+                &mut canvas,
+                (pane.vertical_scroll_bar, pane.horizontal_scroll_bar),
+                (text_width, text_height),
+                (scroll_x, scroll_y),
+                pane.max_scroll,
+                &markers,
+            ));
+        }
+        if let Some(divider) = divider {
+            render_divider(&mut canvas, divider);
         }
-        
 
-        render_text(&mut canvas, &font, &buffer.to_string(), scroll_x, scroll_y);
+        if let Some(completion) = completion.as_mut() {
+            // Keep the selected word in view.
+            let selected = completion.selected_index();
+            let visible_lines = settings::COMPLETION_LINES as usize;
+            if selected < completion.scroll {
+                completion.scroll = selected;
+            } else if selected >= completion.scroll + visible_lines {
+                completion.scroll = selected + 1 - visible_lines;
+            }
+            render_completion(
+                &mut canvas,
+                &font,
+                layout.editor,
+                focused_cursor,
+                &completion.words,
+                (selected, completion.scroll),
+            );
+        }
+
+        let (cursor_row, cursor_col) = document.buffer.get_cursor_position();
 
-        let (cursor_x, cursor_y) =
-            get_cursor_position(&font, &buffer.to_string(), buffer.get_cursor());
+        if let Some(sidebar) = layout.sidebar {
+            // Keep the selection in view when it moved, without undoing scrolling with the wheel.
+            let visible_lines = (sidebar.height().saturating_sub(status_bar_height(&font)) / font.height() as u32).max(1) as usize;
+            let selected = file_explorer.selected_index();
+            if selected != explorer_selected_before || explorer_focused {
+                if selected < file_explorer.scroll {
+                    file_explorer.scroll = selected;
+                } else if selected >= file_explorer.scroll + visible_lines {
+                    file_explorer.scroll = selected + 1 - visible_lines;
+                }
+            }
+            file_explorer.scroll = file_explorer.scroll.min(file_explorer.entry_count().saturating_sub(1));
 
-        render_cursor(
+            let root_name = file_explorer
+                .root()
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().to_string());
+            let (label, text) = match &file_explorer.prompt {
+                Some((ExplorerAction::NewFile, name)) => ("New file", name.as_str()),
+                Some((ExplorerAction::NewFolder, name)) => ("New folder", name.as_str()),
+                Some((ExplorerAction::Rename, name)) => ("Rename", name.as_str()),
+                None => ("Explorer", root_name.as_str()),
+            };
+            let items: Vec<ListItem> = file_explorer
+                .lines()
+                .into_iter()
+                .map(|text| ListItem { text, heading: false, detail: String::new() })
+                .collect();
+            canvas.set_clip_rect(Some(sidebar));
+            render_list_panel(
+                &mut canvas,
+                &font,
+                sidebar,
+                &FindBarRow {
+                    label,
+                    text,
+                    focused: file_explorer.prompt.is_some(),
+                    status: file_explorer.status(),
+                },
+                &[],
+                &items,
+                ((!items.is_empty()).then_some(selected), file_explorer.scroll),
+            );
+            canvas.set_clip_rect(None);
+        }
+
+        if let Some(find_bar) = layout.find_bar {
+            let mut rows = vec![FindBarRow {
+                label: "Find",
+                text: &find.query,
+                focused: find.focus == FindField::Query,
+                status: find.status(),
+            }];
+            if find.replacing {
+                // Preview how many matches a replace-all would change.
+                rows.push(FindBarRow {
+                    label: "Replace",
+                    text: &find.replacement,
+                    focused: find.focus == FindField::Replacement,
+                    status: format!("{} to replace", find.matches().len()),
+                });
+            }
+            render_find_bar(
+                &mut canvas,
+                &font,
+                find_bar,
+                &rows,
+                &[
+                    ("Aa", find.case_sensitive),
+                    ("Word", find.whole_word),
+                    (".*", find.regex),
+                    ("Sel", find.scope().is_some()),
+                ],
+            );
+        }
+
+        if let Some(panel) = layout.bottom_panel {
+            let lines = project_search.lines(&project_root);
+            let selected = lines.iter().position(|(_, index)| *index == Some(project_search.selected_index()));
+            // Keep the selected result in view.
+            if let Some(selected) = selected {
+                let visible_lines = settings::SEARCH_PANEL_LINES as usize;
+                if selected < project_search_scroll {
+                    project_search_scroll = selected.saturating_sub(1);
+                } else if selected >= project_search_scroll + visible_lines {
+                    project_search_scroll = selected + 1 - visible_lines;
+                }
+            }
+            project_search_scroll = project_search_scroll.min(lines.len().saturating_sub(1));
+
+            let items: Vec<ListItem> = lines
+                .into_iter()
+                .map(|(text, index)| ListItem { text, heading: index.is_none(), detail: String::new() })
+                .collect();
+            render_list_panel(
+                &mut canvas,
+                &font,
+                panel,
+                &FindBarRow {
+                    label: "Find in Files",
+                    text: &project_search.query,
+                    focused: true,
+                    status: project_search.status(),
+                },
+                &[("Aa", project_search.case_sensitive), (".*", project_search.regex)],
+                &items,
+                (selected, project_search_scroll),
+            );
+        }
+
+        let position = if let Some(pending) = keymap.pending() {
+            // A chord is waiting for its next key.
+            format!("{} ...", pending)
+        } else if document.selections.count() > 1 {
+            format!("{} cursors  Ln {}, Col {}", document.selections.count(), cursor_row + 1, cursor_col + 1)
+        } else {
+            format!("Ln {}, Col {}", cursor_row + 1, cursor_col + 1)
+        };
+        let position = format!("{}  {}", position, document.indent);
+        render_status_bar(
             &mut canvas,
             &font,
-            cursor_x,
-            cursor_y,
-            cursor_visible,
-            scroll_x,
-            scroll_y,
+            layout.status_bar,
+            &document.title(),
+            &match (&vim, macros.is_recording()) {
+                (Some(vim), true) => format!("Recording  {}  {}", vim.status(), position),
+                (Some(vim), false) => format!("{}  {}", vim.status(), position),
+                (None, true) => format!("Recording  {}", position),
+                (None, false) => position,
+            },
         );
 
-        let (text_width, text_height) = get_text_size(&buffer.to_string(), &font);
+        file_finder_list = None;
+        if file_finder_open {
+            file_finder.update();
+            let line_height = font.height() as u32;
+            let list_height = status_bar_height(&font) + settings::FILE_FINDER_LINES * line_height;
+            let preview_height = settings::FILE_FINDER_PREVIEW_LINES * line_height + 2 * settings::STATUS_BAR_PADDING;
+            let overlay = overlay_rect(layout.editor, (settings::FILE_FINDER_WIDTH, list_height + preview_height));
+            let list = Rect::new(overlay.x(), overlay.y(), overlay.width(), list_height.min(overlay.height()));
 
-        max_scroll_x = if text_width > window_width {
-            text_width - window_width
-        } else {
-            0
-        };
-        max_scroll_y = if text_height > window_height {
-            text_height - window_height
-        } else {
-            0
-        };
+            // Keep the selected file in view.
+            let selected = file_finder.selected_index();
+            let visible_lines = settings::FILE_FINDER_LINES as usize;
+            if selected < file_finder.scroll {
+                file_finder.scroll = selected;
+            } else if selected >= file_finder.scroll + visible_lines {
+                file_finder.scroll = selected + 1 - visible_lines;
+            }
 
-        vertical_handle_height =
-        ((window_height as f32 / text_height as f32) * window_height as f32) as u32;
-        horizontal_handle_width =
-            ((window_width as f32 / text_width as f32) * window_width as f32) as u32;
+            let items: Vec<ListItem> = file_finder
+                .lines()
+                .into_iter()
+                .map(|text| ListItem { text, heading: false, detail: String::new() })
+                .collect();
+            render_list_panel(
+                &mut canvas,
+                &font,
+                list,
+                &FindBarRow {
+                    label: "Open",
+                    text: &file_finder.query,
+                    focused: true,
+                    status: file_finder.status(),
+                },
+                &[],
+                &items,
+                ((!items.is_empty()).then_some(selected), file_finder.scroll),
+            );
+            if overlay.height() > list.height() {
+                let preview = Rect::new(overlay.x(), list.bottom(), overlay.width(), overlay.height() - list.height());
+                render_preview(&mut canvas, &font, preview, file_finder.preview());
+            }
+            file_finder_list = Some(list);
+        }
 
-        (vertical_scroll_bar, horizontal_scroll_bar) = render_scroll_bars(
-            &mut canvas,
-            (window_width, window_height),
-            (settings::SCROLL_BAR_WIDTH, settings::SCROLL_BAR_HEIGHT),
-            (text_width, text_height),
-            (scroll_x, scroll_y),
-            (max_scroll_x, max_scroll_y),
-        );
+        palette_list = None;
+        if palette_open {
+            command_palette.update();
+            let list_height = status_bar_height(&font) + settings::COMMAND_PALETTE_LINES * font.height() as u32;
+            let list = overlay_rect(layout.editor, (settings::COMMAND_PALETTE_WIDTH, list_height));
 
-        can_scroll_vertically_down =
-            text_height > viewport_height && scroll_y < max_scroll_y as i32;
-        can_scroll_vertically_up = text_height > viewport_height && scroll_y > 0;
-        can_scroll_horizontally = text_width > viewport_width && scroll_x < max_scroll_x as i32;
+            // Keep the selected command in view.
+            let selected = command_palette.selected_index();
+            let visible_lines = settings::COMMAND_PALETTE_LINES as usize;
+            if selected < command_palette.scroll {
+                command_palette.scroll = selected;
+            } else if selected >= command_palette.scroll + visible_lines {
+                command_palette.scroll = selected + 1 - visible_lines;
+            }
+
+            let items: Vec<ListItem> = command_palette
+                .lines(|command| keymap.shortcut(command))
+                .into_iter()
+                .map(|(text, detail)| ListItem { text, heading: false, detail })
+                .collect();
+            render_list_panel(
+                &mut canvas,
+                &font,
+                list,
+                &FindBarRow {
+                    label: "Command",
+                    text: &command_palette.query,
+                    focused: true,
+                    status: String::new(),
+                },
+                &[],
+                &items,
+                ((!items.is_empty()).then_some(selected), command_palette.scroll),
+            );
+            palette_list = Some(list);
+        }
+
+        macro_list = None;
+        if let Some((prompt, text)) = &macros.prompt {
+            let list_height = status_bar_height(&font) + settings::COMMAND_PALETTE_LINES * font.height() as u32;
+            let list = overlay_rect(layout.editor, (settings::COMMAND_PALETTE_WIDTH, list_height));
+            let (label, names) = match prompt {
+                MacroPrompt::ReplayTimes => ("Times", Vec::new()),
+                MacroPrompt::Save => ("Save as", macros.matches()),
+                MacroPrompt::Play => ("Play", macros.matches()),
+            };
+
+            // Keep the selected macro in view.
+            let selected = macros.selected_index();
+            let visible_lines = settings::COMMAND_PALETTE_LINES as usize;
+            if selected < macros.scroll {
+                macros.scroll = selected;
+            } else if selected >= macros.scroll + visible_lines {
+                macros.scroll = selected + 1 - visible_lines;
+            }
+
+            let items: Vec<ListItem> = names
+                .into_iter()
+                .map(|text| ListItem { text, heading: false, detail: String::new() })
+                .collect();
+            let selected = (*prompt == MacroPrompt::Play && !items.is_empty()).then_some(selected);
+            render_list_panel(
+                &mut canvas,
+                &font,
+                list,
+                &FindBarRow {
+                    label,
+                    text,
+                    focused: true,
+                    status: String::new(),
+                },
+                &[],
+                &items,
+                (selected, macros.scroll),
+            );
+            macro_list = Some(list);
+        }
 
         canvas.present();
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+
+    let (x, y) = canvas.window().position();
+    let (width, height) = canvas.window().size();
+    session::save_window_state(&session::WindowState { x, y, width, height });
 }
@@ -0,0 +1,190 @@
+use crate::{constants::EOF_CHAR, gap_buffer::GapBuffer, settings};
+
+#[derive(PartialEq)]
+pub enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+pub fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() || c == EOF_CHAR {
+        CharClass::Whitespace
+    } else if is_word_char(c) {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || settings::WORD_CHARACTERS.contains(c)
+}
+
+pub fn is_blank_line(line: &str) -> bool {
+    line.chars().all(|c| c.is_whitespace() || c == EOF_CHAR)
+}
+
+pub fn line_start(buffer: &GapBuffer, index: usize) -> usize {
+    let (row, _) = buffer.get_position_of_index(index);
+    buffer.get_index_at_position(row, 0)
+}
+
+pub fn line_end(buffer: &GapBuffer, index: usize) -> usize {
+    let (row, _) = buffer.get_position_of_index(index);
+    buffer.get_index_at_position(row, usize::MAX)
+}
+
+pub fn first_non_blank(buffer: &GapBuffer, index: usize) -> usize {
+    let start = line_start(buffer, index);
+    buffer
+        .to_string()
+        .chars()
+        .skip(start)
+        .position(|c| c == '\n' || c == EOF_CHAR || !c.is_whitespace())
+        .map_or(start, |offset| start + offset)
+}
+
+// Moves to the first non-blank character of the line, or to the start of the
+// line if the cursor is already there.
+pub fn smart_home(buffer: &GapBuffer, index: usize) -> usize {
+    let start = line_start(buffer, index);
+    if !settings::SMART_HOME {
+        return start;
+    }
+
+    let first_non_blank = first_non_blank(buffer, index);
+    if index == first_non_blank {
+        start
+    } else {
+        first_non_blank
+    }
+}
+
+// Moves to the start of the next word, skipping any whitespace in between.
+pub fn word_right(buffer: &GapBuffer, mut index: usize) -> usize {
+    let chars: Vec<char> = buffer.to_string().chars().collect();
+    let last = buffer.length() - 1;
+
+    while index < last && char_class(chars[index]) == CharClass::Whitespace {
+        index += 1;
+    }
+    if index < last {
+        let class = char_class(chars[index]);
+        while index < last && char_class(chars[index]) == class {
+            index += 1;
+        }
+    }
+    index
+}
+
+// Moves to the start of the current or previous word.
+pub fn word_left(buffer: &GapBuffer, mut index: usize) -> usize {
+    let chars: Vec<char> = buffer.to_string().chars().collect();
+
+    while index > 0 && char_class(chars[index - 1]) == CharClass::Whitespace {
+        index -= 1;
+    }
+    if index > 0 {
+        let class = char_class(chars[index - 1]);
+        while index > 0 && char_class(chars[index - 1]) == class {
+            index -= 1;
+        }
+    }
+    index
+}
+
+// Moves to the start of the next word, like Vim's `w`.
+pub fn next_word_start(buffer: &GapBuffer, mut index: usize) -> usize {
+    let chars: Vec<char> = buffer.to_string().chars().collect();
+    let last = buffer.length() - 1;
+
+    let class = char_class(chars[index]);
+    if class != CharClass::Whitespace {
+        while index < last && char_class(chars[index]) == class {
+            index += 1;
+        }
+    }
+    while index < last && char_class(chars[index]) == CharClass::Whitespace {
+        index += 1;
+    }
+    index
+}
+
+// Moves to the last character of the current or next word, like Vim's `e`.
+pub fn word_end(buffer: &GapBuffer, index: usize) -> usize {
+    let chars: Vec<char> = buffer.to_string().chars().collect();
+    let last = buffer.length() - 1;
+
+    let mut index = index + 1;
+    while index < last && char_class(chars[index]) == CharClass::Whitespace {
+        index += 1;
+    }
+    if index >= last {
+        return last.saturating_sub(1);
+    }
+    let class = char_class(chars[index]);
+    while index + 1 < last && char_class(chars[index + 1]) == class {
+        index += 1;
+    }
+    index
+}
+
+// Returns the range of the word touching `index`, which is empty if there is none.
+pub fn word_at(buffer: &GapBuffer, index: usize) -> (usize, usize) {
+    let last = buffer.length() - 1;
+    let mut start = index;
+    let mut end = index;
+
+    while start > 0 && is_word_char(buffer.get_char(start - 1)) {
+        start -= 1;
+    }
+    while end < last && is_word_char(buffer.get_char(end)) {
+        end += 1;
+    }
+    (start, end)
+}
+
+// Moves to the next blank line that follows a block of text, or to the end of
+// the document.
+pub fn paragraph_down(buffer: &GapBuffer, index: usize) -> usize {
+    let text = buffer.to_string();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let (row, _) = buffer.get_position_of_index(index);
+
+    let mut target = row + 1;
+    while target < lines.len() && is_blank_line(lines[target]) {
+        target += 1;
+    }
+    while target < lines.len() && !is_blank_line(lines[target]) {
+        target += 1;
+    }
+
+    if target >= lines.len() {
+        buffer.length() - 1
+    } else {
+        buffer.get_index_at_position(target, 0)
+    }
+}
+
+// Moves to the previous blank line that precedes a block of text, or to the
+// start of the document.
+pub fn paragraph_up(buffer: &GapBuffer, index: usize) -> usize {
+    let text = buffer.to_string();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let (row, _) = buffer.get_position_of_index(index);
+
+    let mut target = row;
+    while target > 0 && is_blank_line(lines[target - 1]) {
+        target -= 1;
+    }
+    while target > 0 && !is_blank_line(lines[target - 1]) {
+        target -= 1;
+    }
+
+    if target == 0 {
+        0
+    } else {
+        buffer.get_index_at_position(target - 1, 0)
+    }
+}
@@ -0,0 +1,163 @@
+use sdl2::rect::{Point, Rect};
+
+use crate::{document::Document, scrolling::Scroll, selection::Selections, settings};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SplitDirection {
+    SideBySide,
+    Stacked,
+}
+
+// Cursors and scroll position of the pane that does not have focus. The
+// focused pane always uses the document's own, so every edit goes through them.
+pub struct View {
+    pub selections: Selections,
+    pub scroll: Scroll,
+}
+
+impl View {
+    fn of(document: &Document) -> Self {
+        View {
+            selections: document.selections.clone(),
+            scroll: document.scroll,
+        }
+    }
+}
+
+// The editor area: a single pane, or two panes showing the active document
+// side by side or one above the other.
+pub struct Panes {
+    split: Option<SplitDirection>,
+    // Share of the editor area taken by the first pane.
+    ratio: f32,
+    focused: usize,
+    other: Option<View>,
+}
+
+impl Panes {
+    pub fn new() -> Self {
+        Panes {
+            split: None,
+            ratio: 0.5,
+            focused: 0,
+            other: None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        if self.split.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn other(&self) -> Option<&View> {
+        self.other.as_ref()
+    }
+
+    pub fn other_mut(&mut self) -> Option<&mut View> {
+        self.other.as_mut()
+    }
+
+    // Splits the editor in `direction`, starting the new pane where the current
+    // one is. Splitting the same way again goes back to a single pane.
+    pub fn toggle_split(&mut self, direction: SplitDirection, document: &Document) {
+        if self.split == Some(direction) {
+            self.split = None;
+            self.other = None;
+            self.focused = 0;
+        } else {
+            if self.other.is_none() {
+                self.other = Some(View::of(document));
+            }
+            self.split = Some(direction);
+        }
+    }
+
+    // Moves the focus to the pane at `index` by swapping its view with the
+    // document's. Returns false if that pane already has focus.
+    pub fn focus(&mut self, index: usize, document: &mut Document) -> bool {
+        if index == self.focused || index >= self.count() {
+            return false;
+        }
+        if let Some(other) = self.other.as_mut() {
+            std::mem::swap(&mut other.selections, &mut document.selections);
+            std::mem::swap(&mut other.scroll, &mut document.scroll);
+        }
+        self.focused = index;
+        // The text may have changed through the other pane in the meantime.
+        document.selections.clamp(&document.buffer);
+        document.buffer.move_cursor(document.selections.primary().head);
+        true
+    }
+
+    // Shows a newly activated document in the unfocused pane as well.
+    pub fn reset_other(&mut self, document: &Document) {
+        if let Some(other) = self.other.as_mut() {
+            *other = View::of(document);
+        }
+    }
+
+    // Returns the area of each pane and of the divider between them.
+    pub fn areas(&self, editor: Rect) -> (Vec<Rect>, Option<Rect>) {
+        let divider_size = settings::DIVIDER_SIZE;
+        match self.split {
+            None => (vec![editor], None),
+            Some(SplitDirection::SideBySide) => {
+                let available = editor.width().saturating_sub(divider_size);
+                let first = (available as f32 * self.ratio) as u32;
+                let divider_x = editor.x() + first as i32;
+                (
+                    vec![
+                        Rect::new(editor.x(), editor.y(), first.max(1), editor.height()),
+                        Rect::new(
+                            divider_x + divider_size as i32,
+                            editor.y(),
+                            (available - first).max(1),
+                            editor.height(),
+                        ),
+                    ],
+                    Some(Rect::new(divider_x, editor.y(), divider_size, editor.height())),
+                )
+            }
+            Some(SplitDirection::Stacked) => {
+                let available = editor.height().saturating_sub(divider_size);
+                let first = (available as f32 * self.ratio) as u32;
+                let divider_y = editor.y() + first as i32;
+                (
+                    vec![
+                        Rect::new(editor.x(), editor.y(), editor.width(), first.max(1)),
+                        Rect::new(
+                            editor.x(),
+                            divider_y + divider_size as i32,
+                            editor.width(),
+                            (available - first).max(1),
+                        ),
+                    ],
+                    Some(Rect::new(editor.x(), divider_y, editor.width(), divider_size)),
+                )
+            }
+        }
+    }
+
+    // Moves the divider to the mouse, keeping both panes at least MIN_PANE_SIZE.
+    pub fn drag_divider(&mut self, editor: Rect, mouse: Point) {
+        let (start, length, position) = match self.split {
+            Some(SplitDirection::SideBySide) => (editor.x(), editor.width(), mouse.x()),
+            Some(SplitDirection::Stacked) => (editor.y(), editor.height(), mouse.y()),
+            None => return,
+        };
+        let available = length.saturating_sub(settings::DIVIDER_SIZE) as i32;
+        let min_size = settings::MIN_PANE_SIZE as i32;
+        if available < min_size * 2 {
+            return;
+        }
+        let first = (position - start - settings::DIVIDER_SIZE as i32 / 2).clamp(min_size, available - min_size);
+        self.ratio = first as f32 / available as f32;
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+
+use crate::settings;
+
+pub struct LineMatch {
+    pub row: usize,
+    pub column: usize,
+    pub preview: String,
+}
+
+// All matches found in one file.
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+// Searches every file under a directory, skipping whatever `.gitignore` files
+// exclude. The search runs on a background thread and its results are picked
+// up with `poll` while the editor keeps running.
+pub struct ProjectSearch {
+    pub query: String,
+    pub case_sensitive: bool,
+    pub regex: bool,
+    results: Vec<FileMatches>,
+    // Index of the selected match, counting the matches of all files in order.
+    selected: usize,
+    searched_query: Option<String>,
+    error: Option<String>,
+    receiver: Option<Receiver<FileMatches>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ProjectSearch {
+    pub fn new() -> Self {
+        ProjectSearch {
+            query: String::new(),
+            case_sensitive: false,
+            regex: false,
+            results: Vec::new(),
+            selected: 0,
+            searched_query: None,
+            error: None,
+            receiver: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    // Whether the results are for the query as it is typed now.
+    pub fn is_up_to_date(&self) -> bool {
+        self.searched_query.as_ref() == Some(&self.query)
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.results.iter().map(|file| file.matches.len()).sum()
+    }
+
+    // Starts searching `root` for the query, cancelling any search still running.
+    pub fn start(&mut self, root: &Path) {
+        self.cancel();
+        self.results.clear();
+        self.selected = 0;
+        self.error = None;
+        self.searched_query = Some(self.query.clone());
+        if self.query.is_empty() {
+            return;
+        }
+
+        let source = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        let pattern = match RegexBuilder::new(&source)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+        {
+            Ok(pattern) => pattern,
+            Err(_) => {
+                self.error = Some("Invalid regex".to_string());
+                return;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.receiver = Some(receiver);
+        self.cancelled = cancelled.clone();
+
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            for entry in WalkBuilder::new(&root).build().flatten() {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                    continue;
+                }
+                if let Some(file_matches) = search_file(entry.path(), &pattern) {
+                    if sender.send(file_matches).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.receiver = None;
+    }
+
+    // Collects the results found since the last call. Returns true if there were any.
+    pub fn poll(&mut self) -> bool {
+        let receiver = match &self.receiver {
+            Some(receiver) => receiver,
+            None => return false,
+        };
+        let mut received = false;
+        loop {
+            match receiver.try_recv() {
+                Ok(file_matches) => {
+                    self.results.push(file_matches);
+                    received = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    break;
+                }
+            }
+        }
+        received
+    }
+
+    pub fn selected(&self) -> Option<(&Path, &LineMatch)> {
+        self.results
+            .iter()
+            .flat_map(|file| file.matches.iter().map(move |line_match| (file.path.as_path(), line_match)))
+            .nth(self.selected)
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.match_count().saturating_sub(1));
+    }
+
+    pub fn select_by(&mut self, offset: isize) {
+        self.select((self.selected as isize + offset).max(0) as usize);
+    }
+
+    // Returns the lines of the results list: a heading for each file followed by
+    // its matches, each with its index in the selection order.
+    pub fn lines(&self, root: &Path) -> Vec<(String, Option<usize>)> {
+        let mut lines = Vec::new();
+        let mut index = 0;
+        for file in &self.results {
+            let path = file.path.strip_prefix(root).unwrap_or(&file.path);
+            lines.push((path.display().to_string(), None));
+            for line_match in &file.matches {
+                lines.push((format!("  {}: {}", line_match.row + 1, line_match.preview), Some(index)));
+                index += 1;
+            }
+        }
+        lines
+    }
+
+    // Returns e.g. "42 results in 7 files", marked while the search is still running.
+    pub fn status(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        if self.searched_query.is_none() || self.query.is_empty() {
+            return String::new();
+        }
+        let status = format!("{} results in {} files", self.match_count(), self.results.len());
+        if self.is_searching() {
+            format!("{}...", status)
+        } else {
+            status
+        }
+    }
+}
+
+// Returns the matching lines of a file, or None if there are none or the file
+// is not valid text.
+fn search_file(path: &Path, pattern: &Regex) -> Option<FileMatches> {
+    let contents = fs::read_to_string(path).ok()?;
+    let matches: Vec<LineMatch> = contents
+        .lines()
+        .enumerate()
+        .filter_map(|(row, line)| {
+            let found = pattern.find(line)?;
+            Some(LineMatch {
+                row,
+                column: line[..found.start()].chars().count(),
+                preview: line.trim().chars().take(settings::SEARCH_PREVIEW_LENGTH).collect(),
+            })
+        })
+        .collect();
+
+    if matches.is_empty() {
+        None
+    } else {
+        Some(FileMatches {
+            path: path.to_path_buf(),
+            matches,
+        })
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use sdl2::{
     self,
     pixels::Color,
@@ -36,7 +38,7 @@ pub fn render_text(
                 .expect("Failed to render font.");
         } else {
             text_surface = font
-                .render(line)
+                .render(&expand_tabs(line))
                 .blended(Color::WHITE)
                 .expect("Failed to render font.");
         }
@@ -58,6 +60,124 @@ pub fn render_text(
     }
 }
 
+// Replaces each tab with spaces up to the next tab stop, the way the text is drawn.
+pub fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = settings::tab_width - column % settings::tab_width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+fn text_width(font: &Font, text: &str) -> i32 {
+    if text.is_empty() {
+        0
+    } else {
+        font.size_of(text).unwrap().0 as i32
+    }
+}
+
+pub fn render_selections(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    text: &str,
+    selections: &[(usize, usize)],
+    scroll_x: i32,
+    scroll_y: i32,
+) {
+    render_highlights(canvas, font, text, selections, Color::RGB(38, 79, 120), scroll_x, scroll_y);
+}
+
+// Highlights the given `start..end` ranges of the text on the visible lines. A
+// highlighted line break is shown as a space-wide block at the end of its line.
+pub fn render_highlights(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    text: &str,
+    ranges: &[(usize, usize)],
+    color: Color,
+    scroll_x: i32,
+    scroll_y: i32,
+) {
+    let line_height = font.height();
+    let line_break_width = text_width(font, " ");
+    let first_row = (scroll_y / line_height).max(0) as usize;
+    let last_row = ((scroll_y + canvas.viewport().height() as i32) / line_height).max(0) as usize;
+    let mut line_start = 0;
+
+    canvas.set_draw_color(color);
+    for (row, line) in text.split('\n').enumerate() {
+        let line_end = line_start + line.chars().count();
+        if row < first_row {
+            line_start = line_end + 1;
+            continue;
+        }
+        if row > last_row {
+            break;
+        }
+
+        for &(start, end) in ranges {
+            if start == end || start > line_end || end <= line_start {
+                continue;
+            }
+            let from = start.max(line_start) - line_start;
+            let to = end.min(line_end) - line_start;
+            let left: String = line.chars().take(from).collect();
+            let selected: String = line.chars().take(to).collect();
+
+            let x = text_width(font, &expand_tabs(&left));
+            let mut width = text_width(font, &expand_tabs(&selected)) - x;
+            if end > line_end {
+                width += line_break_width;
+            }
+
+            canvas
+                .fill_rect(Rect::new(
+                    x - scroll_x,
+                    row as i32 * line_height - scroll_y,
+                    width.max(1) as u32,
+                    line_height as u32,
+                ))
+                .unwrap();
+        }
+
+        line_start = line_end + 1;
+    }
+}
+
+// Highlights a rectangular selection, including any virtual space past the
+// end of short lines. A zero-width block is drawn as a thin line.
+pub fn render_block_selection(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    rows: RangeInclusive<usize>,
+    (left, right): (usize, usize),
+    scroll_x: i32,
+    scroll_y: i32,
+) {
+    let line_height = font.height();
+    let char_width = font.size_of_char(' ').unwrap().0 as i32;
+    let row_count = rows.end() - rows.start() + 1;
+
+    canvas.set_draw_color(Color::RGB(38, 79, 120));
+    canvas
+        .fill_rect(Rect::new(
+            left as i32 * char_width - scroll_x,
+            *rows.start() as i32 * line_height - scroll_y,
+            (((right - left) as i32 * char_width).max(1)) as u32,
+            (row_count as i32 * line_height) as u32,
+        ))
+        .unwrap();
+}
+
 pub fn render_cursor(
     canvas: &mut Canvas<Window>,
     font: &Font,
@@ -85,60 +205,449 @@ pub fn render_cursor(
         .expect("Failed to render cursor");
 }
 
+// A tick drawn in the vertical scroll bar track, e.g. for the cursor line or a
+// search match. `y` is the position in the text in pixels.
+pub struct ScrollBarMarker {
+    pub y: i32,
+    pub color: Color,
+}
+
+// Returns the offset and length of a scroll bar handle inside its track.
+fn scroll_handle_span(track_length: u32, content_length: u32, scroll: i32, max_scroll: u32) -> (i32, u32) {
+    let handle_length = ((track_length as f32 / content_length as f32) * track_length as f32) as u32;
+    let handle_length = handle_length
+        .max(settings::MIN_SCROLL_HANDLE_SIZE)
+        .min(track_length);
+
+    // The scroll offset may run slightly past its maximum to keep the cursor visible at the end of the longest line.
+    let scroll_ratio = if max_scroll > 0 {
+        (scroll as f32 / max_scroll as f32).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let handle_offset = (scroll_ratio * (track_length - handle_length) as f32) as i32;
+
+    (handle_offset, handle_length)
+}
+
 pub fn render_scroll_bars(
     canvas: &mut Canvas<Window>,
-    (window_width, window_height): (u32, u32),
-    (scroll_bar_width, scroll_bar_height): (u32, u32),
+    (vertical_scroll_bar, horizontal_scroll_bar): (Option<Rect>, Option<Rect>),
     (text_width, text_height): (u32, u32),
     (scroll_x, scroll_y): (i32, i32),
     (max_scroll_x, max_scroll_y): (u32, u32),
-) -> (Rect, Rect) {
-    let vertical_scroll_bar = Rect::new(
-        window_width as i32,
-        0,
-        scroll_bar_width,
-        window_height,
-    );
-    let horizontal_scroll_bar = Rect::new(
-        0,
-        window_height as i32,
-        window_width,
-        scroll_bar_height,
-    );
+    markers: &[ScrollBarMarker],
+) -> (Option<Rect>, Option<Rect>) {
+    let vertical_handle = vertical_scroll_bar.map(|track| {
+        let (handle_y, handle_height) =
+            scroll_handle_span(track.height(), text_height, scroll_y, max_scroll_y);
+        Rect::new(track.x(), track.y() + handle_y, track.width(), handle_height)
+    });
+    let horizontal_handle = horizontal_scroll_bar.map(|track| {
+        let (handle_x, handle_width) =
+            scroll_handle_span(track.width(), text_width, scroll_x, max_scroll_x);
+        Rect::new(track.x() + handle_x, track.y(), handle_width, track.height())
+    });
+
+    for (track, handle) in [(vertical_scroll_bar, vertical_handle), (horizontal_scroll_bar, horizontal_handle)] {
+        if let (Some(track), Some(handle)) = (track, handle) {
+            canvas.set_draw_color(Color::RGB(200, 200, 200));
+            canvas.fill_rect(track).unwrap();
+            canvas.set_draw_color(Color::RGB(100, 100, 100));
+            canvas.fill_rect(handle).unwrap();
+        }
+    }
+
+    if let Some(track) = vertical_scroll_bar {
+        for marker in markers {
+            let marker_y = (marker.y as f32 / text_height.max(1) as f32 * track.height() as f32) as i32;
+            canvas.set_draw_color(marker.color);
+            canvas
+                .fill_rect(Rect::new(track.x(), track.y() + marker_y, track.width(), 2))
+                .unwrap();
+        }
+    }
+
+    (vertical_handle, horizontal_handle)
+}
+
+// Draws the divider between split panes.
+pub fn render_divider(canvas: &mut Canvas<Window>, divider: Rect) {
+    canvas.set_draw_color(Color::RGB(60, 60, 60));
+    canvas.fill_rect(divider).unwrap();
+}
+
+pub fn render_gutter(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    gutter: Rect,
+    line_count: usize,
+    cursor_row: usize,
+    scroll_y: i32,
+) {
+    canvas.set_draw_color(Color::RGB(30, 30, 30));
+    canvas.fill_rect(gutter).unwrap();
+
+    let line_height = font.height();
+    let first_line = (scroll_y / line_height).max(0) as usize;
+    let last_line = ((scroll_y + gutter.height() as i32) / line_height + 1) as usize;
+    let texture_creator = canvas.texture_creator();
+
+    for row in first_line..last_line.min(line_count) {
+        let color = if row == cursor_row {
+            Color::WHITE
+        } else {
+            Color::RGB(120, 120, 120)
+        };
+        let number_surface = font
+            .render(&(row + 1).to_string())
+            .blended(color)
+            .expect("Failed to render font.");
+        let number_texture = texture_creator
+            .create_texture_from_surface(&number_surface)
+            .unwrap();
+
+        let TextureQuery { width, height, .. } = number_texture.query();
+        // Right-align the numbers against the text area.
+        let x = gutter.right() - settings::GUTTER_PADDING as i32 - width as i32;
+        let y = gutter.y() + row as i32 * line_height - scroll_y;
+
+        canvas
+            .copy(&number_texture, None, Some(Rect::new(x, y, width, height)))
+            .unwrap();
+    }
+}
+
+pub fn render_status_bar(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    status_bar: Rect,
+    left_text: &str,
+    right_text: &str,
+) {
+    canvas.set_draw_color(Color::RGB(45, 45, 45));
+    canvas.fill_rect(status_bar).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+
+    for (text, align_right) in [(left_text, false), (right_text, true)] {
+        if text.is_empty() {
+            continue;
+        }
+        let text_surface = font
+            .render(text)
+            .blended(Color::WHITE)
+            .expect("Failed to render font.");
+        let text_texture = texture_creator
+            .create_texture_from_surface(&text_surface)
+            .unwrap();
+
+        let TextureQuery { width, height, .. } = text_texture.query();
+        let x = if align_right {
+            status_bar.right() - padding * 2 - width as i32
+        } else {
+            status_bar.x() + padding * 2
+        };
+        let y = status_bar.y() + padding;
+
+        canvas
+            .copy(&text_texture, None, Some(Rect::new(x, y, width, height)))
+            .unwrap();
+    }
+}
+
+// One line of the find bar: a labelled text field with a status text on the right.
+pub struct FindBarRow<'a> {
+    pub label: &'a str,
+    pub text: &'a str,
+    pub focused: bool,
+    pub status: String,
+}
+
+// Draws the find bar one row per field, with a caret in the focused one. The
+// option toggles go on the first row, with enabled options drawn in white.
+pub fn render_find_bar(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    find_bar: Rect,
+    rows: &[FindBarRow],
+    options: &[(&str, bool)],
+) {
+    canvas.set_draw_color(Color::RGB(37, 37, 38));
+    canvas.fill_rect(find_bar).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+    let row_height = find_bar.height() as i32 / rows.len().max(1) as i32;
+
+    let render_label = |canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, color: Color| {
+        if text.is_empty() {
+            return;
+        }
+        let text_surface = font
+            .render(text)
+            .blended(color)
+            .expect("Failed to render font.");
+        let text_texture = texture_creator
+            .create_texture_from_surface(&text_surface)
+            .unwrap();
+        let TextureQuery { width, height, .. } = text_texture.query();
+        canvas
+            .copy(&text_texture, None, Some(Rect::new(x, y, width, height)))
+            .unwrap();
+    };
 
-    let vertical_handle_height =
-        ((window_height as f32 / text_height as f32) * window_height as f32) as u32;
-    let horizontal_handle_width =
-        ((window_width as f32 / text_width as f32) * window_width as f32) as u32;
-
-    let vertical_handle_y = (scroll_y as f32 / max_scroll_y as f32
-        * ((window_height).saturating_sub(vertical_handle_height)) as f32)
-        as i32;
-    let horizontal_handle_x = (scroll_x as f32 / max_scroll_x as f32
-        * ((window_width).saturating_sub(horizontal_handle_width)) as f32)
-        as i32;
-
-    let vertical_handle = Rect::new(
-        window_width as i32,
-        vertical_handle_y,
-        scroll_bar_width,
-        vertical_handle_height,
+    for (i, row) in rows.iter().enumerate() {
+        let y = find_bar.y() + i as i32 * row_height + padding;
+        let label = format!("{}: {}", row.label, row.text);
+        render_label(canvas, &label, find_bar.x() + padding * 2, y, Color::WHITE);
+        if row.focused {
+            canvas.set_draw_color(Color::WHITE);
+            canvas
+                .fill_rect(Rect::new(
+                    find_bar.x() + padding * 2 + text_width(font, &label),
+                    y,
+                    2,
+                    font.height() as u32,
+                ))
+                .unwrap();
+        }
+
+        // Lay the right-hand labels out from the right edge.
+        let mut x = find_bar.right() - padding * 2 - text_width(font, &row.status);
+        render_label(canvas, &row.status, x, y, Color::WHITE);
+        if i == 0 {
+            for &(name, enabled) in options.iter().rev() {
+                x -= text_width(font, name) + padding * 4;
+                let color = if enabled { Color::WHITE } else { Color::RGB(120, 120, 120) };
+                render_label(canvas, name, x, y, color);
+            }
+        }
+    }
+}
+
+// Draws a tab for each document title, followed by a close button. Returns
+// the area of each tab and of its close button for handling clicks.
+pub fn render_tab_bar(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    tab_bar: Rect,
+    titles: &[String],
+    active: usize,
+) -> Vec<(Rect, Rect)> {
+    canvas.set_draw_color(Color::RGB(30, 30, 30));
+    canvas.fill_rect(tab_bar).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+    let close_width = text_width(font, "x");
+    let mut tabs = Vec::with_capacity(titles.len());
+    let mut x = tab_bar.x();
+
+    for (index, title) in titles.iter().enumerate() {
+        let tab_width = padding * 3 + text_width(font, title) + padding * 3 + close_width + padding * 3;
+        let tab = Rect::new(x, tab_bar.y(), tab_width as u32, tab_bar.height());
+        let color = if index == active { Color::RGB(0, 0, 0) } else { Color::RGB(45, 45, 45) };
+        canvas.set_draw_color(color);
+        canvas.fill_rect(Rect::new(x, tab_bar.y(), tab_width as u32 - 1, tab_bar.height())).unwrap();
+
+        let close = Rect::new(tab.right() - padding * 3 - close_width, tab_bar.y(), close_width as u32, tab_bar.height());
+        for (text, text_x, color) in [
+            (title.as_str(), x + padding * 3, Color::WHITE),
+            ("x", close.x(), Color::RGB(150, 150, 150)),
+        ] {
+            let text_surface = font
+                .render(text)
+                .blended(color)
+                .expect("Failed to render font.");
+            let text_texture = texture_creator
+                .create_texture_from_surface(&text_surface)
+                .unwrap();
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(&text_texture, None, Some(Rect::new(text_x, tab_bar.y() + padding, width, height)))
+                .unwrap();
+        }
+
+        tabs.push((tab, close));
+        x += tab_width;
+    }
+    tabs
+}
+
+pub struct ListItem {
+    pub text: String,
+    // Headings group the items below them and are drawn dimmed.
+    pub heading: bool,
+    // Drawn dimmed at the right edge, e.g. a command's shortcut.
+    pub detail: String,
+}
+
+// Draws a panel with an input row at the top and a list below it, starting at
+// `first_item`. The selected item is highlighted.
+pub fn render_list_panel(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    panel: Rect,
+    input: &FindBarRow,
+    options: &[(&str, bool)],
+    items: &[ListItem],
+    (selected, first_item): (Option<usize>, usize),
+) {
+    let line_height = font.height();
+    let input_height = (line_height as u32 + 2 * settings::STATUS_BAR_PADDING).min(panel.height());
+    render_find_bar(
+        canvas,
+        font,
+        Rect::new(panel.x(), panel.y(), panel.width(), input_height),
+        std::slice::from_ref(input),
+        options,
     );
-    let horizontal_handle = Rect::new(
-        horizontal_handle_x,
-        window_height as i32,
-        horizontal_handle_width,
-        scroll_bar_height,
+
+    let list = Rect::new(
+        panel.x(),
+        panel.y() + input_height as i32,
+        panel.width(),
+        (panel.height() - input_height).max(1),
     );
+    canvas.set_draw_color(Color::RGB(25, 25, 26));
+    canvas.fill_rect(list).unwrap();
 
-    canvas.set_draw_color(Color::RGB(200, 200, 200));
-    canvas.fill_rect(vertical_scroll_bar).unwrap();
-    canvas.fill_rect(horizontal_scroll_bar).unwrap();
-    canvas.set_draw_color(Color::RGB(100, 100, 100));
-    canvas.fill_rect(vertical_handle).unwrap();
-    canvas.fill_rect(horizontal_handle).unwrap();
+    let texture_creator = canvas.texture_creator();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+    let visible_items = (list.height() as i32 / line_height) as usize;
 
-    (vertical_handle, horizontal_handle)
+    for (row, (index, item)) in items.iter().enumerate().skip(first_item).take(visible_items).enumerate() {
+        let y = list.y() + row as i32 * line_height;
+        if selected == Some(index) {
+            canvas.set_draw_color(Color::RGB(38, 79, 120));
+            canvas
+                .fill_rect(Rect::new(list.x(), y, list.width(), line_height as u32))
+                .unwrap();
+        }
+        if item.text.is_empty() {
+            continue;
+        }
+        let color = if item.heading { Color::RGB(160, 160, 160) } else { Color::WHITE };
+        let text_surface = font
+            .render(&item.text)
+            .blended(color)
+            .expect("Failed to render font.");
+        let text_texture = texture_creator
+            .create_texture_from_surface(&text_surface)
+            .unwrap();
+        let TextureQuery { width, height, .. } = text_texture.query();
+        canvas
+            .copy(&text_texture, None, Some(Rect::new(list.x() + padding * 2, y, width, height)))
+            .unwrap();
+
+        if !item.detail.is_empty() {
+            let detail_surface = font
+                .render(&item.detail)
+                .blended(Color::RGB(140, 140, 140))
+                .expect("Failed to render font.");
+            let detail_texture = texture_creator
+                .create_texture_from_surface(&detail_surface)
+                .unwrap();
+            let TextureQuery { width, height, .. } = detail_texture.query();
+            let x = list.right() - padding * 2 - width as i32;
+            canvas
+                .copy(&detail_texture, None, Some(Rect::new(x, y, width, height)))
+                .unwrap();
+        }
+    }
+}
+
+// Draws a read-only excerpt of a file, e.g. the file finder's preview of the
+// selected file.
+pub fn render_preview(canvas: &mut Canvas<Window>, font: &Font, area: Rect, text: &str) {
+    canvas.set_draw_color(Color::RGB(30, 30, 30));
+    canvas.fill_rect(area).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+    canvas.set_clip_rect(Some(area));
+    for (row, line) in text.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let text_surface = font
+            .render(&expand_tabs(line))
+            .blended(Color::RGB(190, 190, 190))
+            .expect("Failed to render font.");
+        let text_texture = texture_creator
+            .create_texture_from_surface(&text_surface)
+            .unwrap();
+        let TextureQuery { width, height, .. } = text_texture.query();
+        let y = area.y() + padding + row as i32 * font.height();
+        canvas
+            .copy(&text_texture, None, Some(Rect::new(area.x() + padding * 2, y, width, height)))
+            .unwrap();
+    }
+    canvas.set_clip_rect(None);
+}
+
+// Draws the word completion popup under the cursor at `cursor`, or above it
+// when there is no room below, keeping it inside `area`.
+pub fn render_completion(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    area: Rect,
+    cursor: (i32, i32),
+    words: &[String],
+    (selected, first_word): (usize, usize),
+) {
+    let line_height = font.height();
+    let padding = settings::STATUS_BAR_PADDING as i32;
+    let visible_words = words.len().min(settings::COMPLETION_LINES as usize);
+    let width = words.iter().map(|word| text_width(font, word)).max().unwrap_or(0) + padding * 4;
+    let height = visible_words as i32 * line_height;
+
+    let (cursor_x, cursor_y) = cursor;
+    let y = if cursor_y + line_height + height > area.bottom() {
+        cursor_y - height
+    } else {
+        cursor_y + line_height
+    };
+    let x = cursor_x.min(area.right() - width).max(area.x());
+    let popup = Rect::new(x, y, width as u32, height.max(1) as u32);
+    canvas.set_draw_color(Color::RGB(37, 37, 38));
+    canvas.fill_rect(popup).unwrap();
+    canvas.set_draw_color(Color::RGB(69, 69, 69));
+    canvas.draw_rect(popup).unwrap();
+
+    let texture_creator = canvas.texture_creator();
+    for (row, (index, word)) in words.iter().enumerate().skip(first_word).take(visible_words).enumerate() {
+        let y = popup.y() + row as i32 * line_height;
+        if index == selected {
+            canvas.set_draw_color(Color::RGB(38, 79, 120));
+            canvas
+                .fill_rect(Rect::new(popup.x(), y, popup.width(), line_height as u32))
+                .unwrap();
+        }
+        let text_surface = font
+            .render(word)
+            .blended(Color::WHITE)
+            .expect("Failed to render font.");
+        let text_texture = texture_creator
+            .create_texture_from_surface(&text_surface)
+            .unwrap();
+        let TextureQuery { width, height, .. } = text_texture.query();
+        canvas
+            .copy(&text_texture, None, Some(Rect::new(popup.x() + padding * 2, y, width, height)))
+            .unwrap();
+    }
+}
+
+// Returns the index of the list item at `y` in a panel drawn by `render_list_panel`.
+pub fn get_list_item_at(font: &Font, panel: Rect, y: i32, first_item: usize) -> Option<usize> {
+    let line_height = font.height();
+    let list_y = panel.y() + line_height + 2 * settings::STATUS_BAR_PADDING as i32;
+    if y < list_y {
+        return None;
+    }
+    Some(first_item + ((y - list_y) / line_height) as usize)
 }
 
 pub fn get_cursor_position(font: &Font, text: &str, cursor_index: usize) -> (i32, i32) {
@@ -149,8 +658,8 @@ pub fn get_cursor_position(font: &Font, text: &str, cursor_index: usize) -> (i32
 
     for line in lines {
         if current_index + line.chars().count() >= cursor_index {
-            let (left, _) = text.split_at(cursor_index - current_index);
-            cursor_x = font.size_of(left).unwrap().0 as i32;
+            let left: String = line.chars().take(cursor_index - current_index).collect();
+            cursor_x = text_width(font, &expand_tabs(&left));
             break;
         }
         cursor_y += font.height() as i32;
@@ -165,13 +674,23 @@ pub fn get_text_size(text: &str, font: &Font) -> (u32, u32) {
     let mut text_height = 0;
 
     for line in lines {
-        let (line_width, line_height) = font.size_of(line).unwrap();
+        let (line_width, line_height) = font.size_of(&expand_tabs(line)).unwrap();
         text_width = text_width.max(line_width);
         text_height += line_height
     }
     (text_width, text_height)
 }
 
+// Returns the row and column nearest to a point in the text, without clamping
+// the column to the length of the line.
+pub fn get_nearest_grid_position(font: &Font, x: i32, y: i32) -> (usize, usize) {
+    let line_height = font.height();
+    let char_width = font.size_of_char(' ').unwrap().0 as i32;
+    let row = y.max(0) / line_height;
+    let col = (x.max(0) + char_width / 2) / char_width;
+    (row as usize, col as usize)
+}
+
 pub fn get_nearest_character_position(font: &Font, text: &str, x: i32, y: i32) -> usize {
     let lines: Vec<&str> = text.split('\n').collect();
     let mut nearest_char_index = 0;
@@ -185,7 +704,7 @@ pub fn get_nearest_character_position(font: &Font, text: &str, x: i32, y: i32) -
 
     for (char_index, _) in line.char_indices() {
         let (left, _) = line.split_at(char_index);
-        let char_x = font.size_of(left).unwrap().0 as i32;
+        let char_x = text_width(font, &expand_tabs(left));
 
         if char_x > x {
             nearest_char_index = current_index + char_index;
@@ -205,4 +724,3 @@ pub fn get_nearest_character_position(font: &Font, text: &str, x: i32, y: i32) -
     nearest_char_index += current_index;
     nearest_char_index
 }
-
@@ -0,0 +1,152 @@
+use sdl2::ttf::Font;
+
+use crate::settings;
+
+// Scroll offsets of a view. With smooth scrolling enabled the offsets ease
+// towards their target over a few frames instead of jumping there.
+#[derive(Clone, Copy, Default)]
+pub struct Scroll {
+    x: f32,
+    y: f32,
+    target_x: i32,
+    target_y: i32,
+}
+
+impl Scroll {
+    pub fn position(&self) -> (i32, i32) {
+        (self.x.round() as i32, self.y.round() as i32)
+    }
+
+    pub fn target(&self) -> (i32, i32) {
+        (self.target_x, self.target_y)
+    }
+
+    pub fn scroll_to(&mut self, (x, y): (i32, i32)) {
+        self.target_x = x;
+        self.target_y = y;
+        if !settings::SMOOTH_SCROLLING {
+            self.jump_to((x, y));
+        }
+    }
+
+    // Moves the view immediately, e.g. while dragging a scroll bar handle.
+    pub fn jump_to(&mut self, (x, y): (i32, i32)) {
+        self.target_x = x;
+        self.target_y = y;
+        self.x = x as f32;
+        self.y = y as f32;
+    }
+
+    // Scrolls relative to the current target. Only the axes that actually move
+    // are clamped, so an offset past the maximum that keeps the cursor visible
+    // is not undone by scrolling along the other axis.
+    pub fn scroll_by(&mut self, (dx, dy): (i32, i32), (max_x, max_y): (u32, u32)) {
+        let (mut x, mut y) = self.target();
+        if dx != 0 {
+            x = (x + dx).clamp(0, (max_x as i32).max(x));
+        }
+        if dy != 0 {
+            y = (y + dy).clamp(0, (max_y as i32).max(y));
+        }
+        self.scroll_to((x, y));
+    }
+
+    // Advances the smooth scrolling animation by one frame.
+    pub fn update(&mut self) {
+        let ease = |current: f32, target: i32| {
+            let distance = target as f32 - current;
+            if distance.abs() < 1.0 {
+                target as f32
+            } else {
+                current + distance * settings::SMOOTH_SCROLL_SPEED
+            }
+        };
+        self.x = ease(self.x, self.target_x);
+        self.y = ease(self.y, self.target_y);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Recenter {
+    Center,
+    Top,
+    Bottom,
+}
+
+impl Recenter {
+    // Repeated recenter commands cycle center -> top -> bottom.
+    pub fn next(self) -> Self {
+        match self {
+            Recenter::Center => Recenter::Top,
+            Recenter::Top => Recenter::Bottom,
+            Recenter::Bottom => Recenter::Center,
+        }
+    }
+}
+
+fn scroll_margins(font: &Font, (view_width, view_height): (u32, u32)) -> (i32, i32) {
+    let line_height = font.height();
+    let char_width = font.size_of_char(' ').unwrap().0 as i32;
+
+    // Never let the margins take up more than half of the view, otherwise the
+    // cursor could not be placed anywhere without scrolling again.
+    let margin_y = (settings::SCROLL_OFF_LINES as i32 * line_height)
+        .min((view_height as i32 - line_height).max(0) / 2);
+    let margin_x = (settings::SCROLL_OFF_COLUMNS as i32 * char_width)
+        .min((view_width as i32 - char_width).max(0) / 2);
+
+    (margin_x, margin_y)
+}
+
+// Returns the scroll offsets needed to keep the cursor inside the view,
+// keeping at least the configured scroll-off margin around it.
+pub fn scroll_to_cursor(
+    font: &Font,
+    (cursor_x, cursor_y): (i32, i32),
+    (view_width, view_height): (u32, u32),
+    (text_width, text_height): (u32, u32),
+    (mut scroll_x, mut scroll_y): (i32, i32),
+) -> (i32, i32) {
+    let line_height = font.height();
+    let (margin_x, margin_y) = scroll_margins(font, (view_width, view_height));
+
+    if cursor_y - margin_y < scroll_y {
+        scroll_y = cursor_y - margin_y;
+    } else if cursor_y + line_height + margin_y > scroll_y + view_height as i32 {
+        scroll_y = cursor_y + line_height + margin_y - view_height as i32;
+    }
+
+    if cursor_x - margin_x < scroll_x {
+        scroll_x = cursor_x - margin_x;
+    } else if cursor_x + margin_x > scroll_x + view_width as i32 {
+        scroll_x = cursor_x + margin_x - view_width as i32;
+    }
+
+    // Allow scrolling a margin past the longest line so the cursor stays visible
+    // while typing at its end.
+    let max_scroll_x = (text_width as i32 + margin_x - view_width as i32).max(0);
+    let max_scroll_y = (text_height as i32 - view_height as i32).max(0);
+
+    (scroll_x.clamp(0, max_scroll_x), scroll_y.clamp(0, max_scroll_y))
+}
+
+// Returns the vertical scroll offset that puts the cursor line at the given
+// position of the view.
+pub fn recenter(
+    font: &Font,
+    cursor_y: i32,
+    view_height: u32,
+    text_height: u32,
+    position: Recenter,
+) -> i32 {
+    let line_height = font.height();
+    let (_, margin_y) = scroll_margins(font, (0, view_height));
+
+    let scroll_y = match position {
+        Recenter::Center => cursor_y + line_height / 2 - view_height as i32 / 2,
+        Recenter::Top => cursor_y - margin_y,
+        Recenter::Bottom => cursor_y + line_height + margin_y - view_height as i32,
+    };
+
+    scroll_y.clamp(0, (text_height as i32 - view_height as i32).max(0))
+}
@@ -0,0 +1,472 @@
+use crate::{
+    gap_buffer::{Change, GapBuffer},
+    motions,
+};
+
+// An edit replacing a range with text, leaving the cursor this many characters into the text.
+pub type Edit = (usize, usize, String, usize);
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+    // Column that vertical motions aim for, kept while passing shorter lines.
+    pub goal_column: Option<usize>,
+}
+
+impl Selection {
+    pub fn cursor(index: usize) -> Self {
+        Selection {
+            anchor: index,
+            head: index,
+            goal_column: None,
+        }
+    }
+
+    pub fn range(anchor: usize, head: usize) -> Self {
+        Selection {
+            anchor,
+            head,
+            goal_column: None,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+// All cursors of the editor, each with its own selection. The selections are
+// kept sorted and never overlap. The primary selection is the one the view
+// follows, and its head is always where the gap buffer's cursor sits.
+#[derive(Clone)]
+pub struct Selections {
+    selections: Vec<Selection>,
+    primary: usize,
+}
+
+impl Selections {
+    pub fn new() -> Self {
+        Selections {
+            selections: vec![Selection::cursor(0)],
+            primary: 0,
+        }
+    }
+
+    pub fn primary(&self) -> Selection {
+        self.selections[self.primary]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Selection> {
+        self.selections.iter()
+    }
+
+    pub fn count(&self) -> usize {
+        self.selections.len()
+    }
+
+    pub fn set_single(&mut self, buffer: &mut GapBuffer, selection: Selection) {
+        self.selections = vec![selection];
+        self.primary = 0;
+        self.sync(buffer);
+    }
+
+    // Places a cursor at each of the given positions, e.g. after an undo.
+    pub fn set_cursors(&mut self, buffer: &mut GapBuffer, positions: &[usize]) {
+        if positions.is_empty() {
+            return;
+        }
+        let last = buffer.length() - 1;
+        self.selections = positions
+            .iter()
+            .map(|&position| Selection::cursor(position.min(last)))
+            .collect();
+        self.primary = self.selections.len() - 1;
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Replaces the selections, making the last one primary.
+    pub fn set_all(&mut self, buffer: &mut GapBuffer, selections: Vec<Selection>) {
+        if selections.is_empty() {
+            return;
+        }
+        self.selections = selections;
+        self.primary = self.selections.len() - 1;
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Adds a selection and makes it the primary one.
+    pub fn add(&mut self, buffer: &mut GapBuffer, selection: Selection) {
+        self.selections.push(selection);
+        self.primary = self.selections.len() - 1;
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Adds a cursor at `index`, or removes the cursor already there as long as
+    // it is not the last one.
+    pub fn toggle_cursor(&mut self, buffer: &mut GapBuffer, index: usize) {
+        let existing = self
+            .selections
+            .iter()
+            .position(|selection| selection.is_empty() && selection.head == index);
+        match existing {
+            Some(existing) if self.selections.len() > 1 => {
+                self.selections.remove(existing);
+                self.primary = self.selections.len() - 1;
+                self.sync(buffer);
+            }
+            Some(_) => {}
+            None => self.add(buffer, Selection::cursor(index)),
+        }
+    }
+
+    // Drops every cursor but the primary one and clears its selection.
+    // Returns false if there was nothing to collapse.
+    pub fn collapse(&mut self, buffer: &mut GapBuffer) -> bool {
+        let primary = self.primary();
+        if self.selections.len() == 1 && primary.is_empty() {
+            return false;
+        }
+        self.set_single(buffer, Selection::cursor(primary.head));
+        true
+    }
+
+    // Moves the head of every selection with `motion`. Without `extend` the
+    // anchors follow, leaving plain cursors.
+    pub fn move_each(
+        &mut self,
+        buffer: &mut GapBuffer,
+        extend: bool,
+        motion: impl Fn(&GapBuffer, usize) -> usize,
+    ) {
+        let last = buffer.length() - 1;
+        for selection in self.selections.iter_mut() {
+            selection.head = motion(buffer, selection.head).min(last);
+            if !extend {
+                selection.anchor = selection.head;
+            }
+            selection.goal_column = None;
+        }
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Moves every selection head by `rows` lines, aiming for its goal column.
+    pub fn move_each_vertically(&mut self, buffer: &mut GapBuffer, extend: bool, rows: isize) {
+        let last_row = buffer.get_line_count() - 1;
+        for selection in self.selections.iter_mut() {
+            let (row, col) = buffer.get_position_of_index(selection.head);
+            let goal_column = selection.goal_column.unwrap_or(col);
+            let target_row = (row as isize + rows).clamp(0, last_row as isize) as usize;
+            if target_row == row {
+                continue;
+            }
+
+            selection.head = buffer.get_index_at_position(target_row, goal_column);
+            if !extend {
+                selection.anchor = selection.head;
+            }
+            selection.goal_column = Some(goal_column);
+        }
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Adds a cursor on the line above the topmost cursor, or below the bottommost one.
+    pub fn add_cursor_vertically(&mut self, buffer: &mut GapBuffer, up: bool) {
+        let edge = if up {
+            self.selections[0]
+        } else {
+            self.selections[self.selections.len() - 1]
+        };
+        let (row, col) = buffer.get_position_of_index(edge.head);
+        let goal_column = edge.goal_column.unwrap_or(col);
+
+        let target_row = if up {
+            match row.checked_sub(1) {
+                Some(target_row) => target_row,
+                None => return,
+            }
+        } else if row + 1 < buffer.get_line_count() {
+            row + 1
+        } else {
+            return;
+        };
+
+        let mut cursor = Selection::cursor(buffer.get_index_at_position(target_row, goal_column));
+        cursor.goal_column = Some(goal_column);
+        self.add(buffer, cursor);
+    }
+
+    // Selects the word under each empty cursor. Once the primary selection is
+    // not empty, adds a selection at the next occurrence of its text instead.
+    pub fn add_next_occurrence(&mut self, buffer: &mut GapBuffer) {
+        if self.primary().is_empty() {
+            for selection in self.selections.iter_mut().filter(|selection| selection.is_empty()) {
+                let (start, end) = motions::word_at(buffer, selection.head);
+                selection.anchor = start;
+                selection.head = end;
+            }
+            self.normalize();
+            self.sync(buffer);
+            return;
+        }
+
+        let text: Vec<char> = buffer.to_string().chars().collect();
+        let content = &text[..buffer.length() - 1];
+        let primary = self.primary();
+        let needle = &content[primary.start()..primary.end()];
+
+        // Search forward from the primary selection, wrapping around the end of the text.
+        let next = (primary.end()..content.len())
+            .chain(0..primary.start())
+            .filter(|&start| content[start..].starts_with(needle))
+            .find(|&start| self.selections.iter().all(|selection| selection.start() != start));
+
+        if let Some(start) = next {
+            self.add(
+                buffer,
+                Selection {
+                    anchor: start,
+                    head: start + needle.len(),
+                    goal_column: None,
+                },
+            );
+        }
+    }
+
+    // The rows with a selection or cursor on them, in order. A selection
+    // ending at the start of a line leaves that line out.
+    pub fn rows(&self, buffer: &GapBuffer) -> Vec<usize> {
+        let mut rows: Vec<usize> = Vec::new();
+        for selection in &self.selections {
+            let (first, _) = buffer.get_position_of_index(selection.start());
+            let (mut last, column) = buffer.get_position_of_index(selection.end());
+            if column == 0 && last > first {
+                last -= 1;
+            }
+            rows.extend(first..=last);
+        }
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+
+    pub fn primary_text(&self, buffer: &GapBuffer) -> String {
+        let primary = self.primary();
+        (primary.start()..primary.end()).map(|i| buffer.get_char(i)).collect()
+    }
+
+    pub fn selected_texts(&self, buffer: &GapBuffer) -> Vec<String> {
+        self.selections
+            .iter()
+            .map(|selection| (selection.start()..selection.end()).map(|i| buffer.get_char(i)).collect())
+            .collect()
+    }
+
+    // Replaces every selection with `text`, leaving a cursor after each insertion.
+    pub fn insert_text(&mut self, buffer: &mut GapBuffer, text: &str) {
+        self.edit_each(buffer, &[text], |_, selection| (selection.start(), selection.end()));
+    }
+
+    // Replaces each selection with its own text, e.g. when pasting one line per cursor.
+    pub fn insert_each(&mut self, buffer: &mut GapBuffer, texts: &[&str]) {
+        self.edit_each(buffer, texts, |_, selection| (selection.start(), selection.end()));
+    }
+
+    // Deletes every selection, or the character after each empty cursor.
+    pub fn delete_forward(&mut self, buffer: &mut GapBuffer) {
+        self.edit_each(buffer, &[""], |buffer, selection| {
+            if selection.is_empty() {
+                (selection.head, (selection.head + 1).min(buffer.length() - 1))
+            } else {
+                (selection.start(), selection.end())
+            }
+        });
+    }
+
+    // Replaces the range returned by `range_of` for each selection with the
+    // matching entry of `texts`, or with its only entry, as a single undo step.
+    fn edit_each(
+        &mut self,
+        buffer: &mut GapBuffer,
+        texts: &[&str],
+        range_of: impl Fn(&GapBuffer, &Selection) -> (usize, usize),
+    ) {
+        let edits: Vec<Edit> = self
+            .selections
+            .iter()
+            .enumerate()
+            .map(|(i, selection)| {
+                let (start, end) = range_of(buffer, selection);
+                let text = if texts.len() == 1 { texts[0] } else { texts[i] };
+                (start, end, text.to_string(), text.chars().count())
+            })
+            .collect();
+        self.edit_all(buffer, edits);
+    }
+
+    // Makes the edit returned by `edit_of` for each selection as a single undo
+    // step, e.g. a line break with the indentation that fits each line.
+    pub fn edit_with(&mut self, buffer: &mut GapBuffer, edit_of: impl Fn(&GapBuffer, &Selection) -> Edit) {
+        let edits: Vec<Edit> = self.selections.iter().map(|selection| edit_of(buffer, selection)).collect();
+        self.edit_all(buffer, edits);
+    }
+
+    // Makes one edit per selection, in the order of the selections.
+    pub fn edit_all(&mut self, buffer: &mut GapBuffer, edits: Vec<Edit>) {
+        buffer.begin_undo_group();
+        // Earlier edits shift the positions of everything after them.
+        let mut shift: isize = 0;
+        let mut previous_end = 0;
+        for (selection, (start, end, text, cursor)) in self.selections.iter_mut().zip(edits) {
+            let inserted = text.chars().count();
+            let start = start.max(previous_end);
+            let end = end.max(start);
+            previous_end = end;
+
+            let shifted_start = (start as isize + shift) as usize;
+            let shifted_end = (end as isize + shift) as usize;
+            buffer.delete_range(shifted_start, shifted_end);
+            buffer.insert_str(shifted_start, &text);
+
+            *selection = Selection::cursor(shifted_start + cursor);
+            shift += inserted as isize - (end - start) as isize;
+        }
+        buffer.end_undo_group();
+
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Replaces every selection with the one `f` makes of it, e.g. to keep the
+    // selections on the same text after editing around them.
+    pub fn map_each(&mut self, buffer: &mut GapBuffer, f: impl Fn(Selection) -> Selection) {
+        let last = buffer.length() - 1;
+        for selection in &mut self.selections {
+            let mapped = f(*selection);
+            *selection = Selection::range(mapped.anchor.min(last), mapped.head.min(last));
+        }
+        self.normalize();
+        self.sync(buffer);
+    }
+
+    // Makes edits that are not tied to a selection, e.g. to the indentation of
+    // lines, as a single undo step. Each edit removes a number of characters at
+    // a position and inserts text there, in order of position. The selections
+    // stay on the same text, and one starting where text is inserted takes it in.
+    pub fn edit_around(&mut self, buffer: &mut GapBuffer, edits: &[(usize, usize, &str)]) {
+        if edits.is_empty() {
+            return;
+        }
+        debug_assert!(
+            edits.windows(2).all(|pair| pair[0].0 + pair[0].1 <= pair[1].0),
+            "edits must be sorted by position and not overlap"
+        );
+        buffer.begin_undo_group();
+        for &(start, removed, text) in edits.iter().rev() {
+            buffer.delete_range(start, start + removed);
+            buffer.insert_str(start, text);
+        }
+        buffer.end_undo_group();
+
+        let map = |position: usize, stays_at_start: bool| {
+            let mut shifted = position as isize;
+            for &(start, removed, text) in edits {
+                if position > start || (position == start && !stays_at_start) {
+                    shifted += text.chars().count() as isize - removed.min(position - start) as isize;
+                }
+            }
+            shifted as usize
+        };
+        self.map_each(buffer, |selection| {
+            let empty = selection.is_empty();
+            let anchor = map(selection.anchor, !empty && selection.anchor < selection.head);
+            let head = map(selection.head, !empty && selection.head < selection.anchor);
+            Selection::range(anchor, head)
+        });
+    }
+
+    // Keeps the selections on the same text through changes made without them,
+    // e.g. through another view of the same buffer. Like with `edit_around`, a
+    // selection starting where text is inserted takes it in.
+    pub fn follow_changes(&mut self, buffer: &GapBuffer, changes: &[Change]) {
+        for selection in &mut self.selections {
+            let empty = selection.is_empty();
+            let anchor_first = selection.anchor < selection.head;
+            for change in changes {
+                selection.anchor = change.map(selection.anchor, !empty && anchor_first);
+                selection.head = change.map(selection.head, !empty && !anchor_first);
+            }
+        }
+        self.clamp(buffer);
+    }
+
+    // Pulls selections past the end of the text back inside it, e.g. after the
+    // text was edited through another view of the same buffer.
+    pub fn clamp(&mut self, buffer: &GapBuffer) {
+        let last = buffer.length() - 1;
+        for selection in &mut self.selections {
+            selection.anchor = selection.anchor.min(last);
+            selection.head = selection.head.min(last);
+        }
+        self.normalize();
+    }
+
+    // Sorts the selections and merges the ones that touch or overlap, keeping
+    // track of which one is primary.
+    fn normalize(&mut self) {
+        let primary = self.selections[self.primary];
+        self.selections.sort_by_key(|selection| (selection.start(), selection.end()));
+
+        let mut merged: Vec<Selection> = Vec::with_capacity(self.selections.len());
+        self.primary = 0;
+        for selection in self.selections.drain(..) {
+            if let Some(last) = merged.last_mut() {
+                let overlaps = selection.start() < last.end()
+                    || (selection.start() == last.end() && (selection.is_empty() || last.is_empty()));
+                if overlaps {
+                    let start = last.start();
+                    let end = last.end().max(selection.end());
+                    // Keep the direction of whichever selection is not just a cursor.
+                    let backward = if selection.is_empty() {
+                        last.head < last.anchor
+                    } else {
+                        selection.head < selection.anchor
+                    };
+                    if backward {
+                        last.anchor = end;
+                        last.head = start;
+                    } else {
+                        last.anchor = start;
+                        last.head = end;
+                    }
+                    if selection == primary {
+                        self.primary = merged.len() - 1;
+                    }
+                    continue;
+                }
+            }
+            if selection == primary {
+                self.primary = merged.len();
+            }
+            merged.push(selection);
+        }
+        self.selections = merged;
+    }
+
+    fn sync(&self, buffer: &mut GapBuffer) {
+        buffer.move_cursor(self.primary().head);
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::settings;
+
+const SESSION_FILE: &str = "session";
+
+// Window geometry remembered between runs of the editor.
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn session_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(SESSION_FILE))
+}
+
+// Reads the window state saved by the last session. A missing or malformed
+// session file simply means the editor starts with its default geometry.
+pub fn load_window_state() -> Option<WindowState> {
+    let contents = fs::read_to_string(session_path()?).ok()?;
+
+    let mut x: Option<i32> = None;
+    let mut y: Option<i32> = None;
+    let mut width: Option<u32> = None;
+    let mut height: Option<u32> = None;
+
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "window_x" => x = value.parse().ok(),
+            "window_y" => y = value.parse().ok(),
+            "window_width" => width = value.parse().ok(),
+            "window_height" => height = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    Some(WindowState {
+        x: x?,
+        y: y?,
+        width: width?.max(settings::MIN_WINDOW_WIDTH),
+        height: height?.max(settings::MIN_WINDOW_HEIGHT),
+    })
+}
+
+pub fn save_window_state(state: &WindowState) {
+    let Some(path) = session_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Unable to create config directory: {:?}", e);
+            return;
+        }
+    }
+
+    let contents = format!(
+        "window_x={}\nwindow_y={}\nwindow_width={}\nwindow_height={}\n",
+        state.x, state.y, state.width, state.height
+    );
+    if let Err(e) = fs::write(&path, contents) {
+        eprintln!("Unable to save session: {:?}", e);
+    }
+}
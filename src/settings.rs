@@ -1,9 +1,91 @@
+use std::path::PathBuf;
+
 pub static tab_width: usize = 4;
 pub static font_size: u16 = 20;
 pub static debug_mode: bool = true;
 
 pub static SCROLL_BAR_WIDTH: u32 = 10;
 pub static SCROLL_BAR_HEIGHT: u32 = 10;
+// Keeps scroll bar handles grabbable in very long files.
+pub static MIN_SCROLL_HANDLE_SIZE: u32 = 20;
 
 pub static WINDOW_WIDTH: u32 = 1600;
-pub static WINDOW_HEIGHT: u32 = 1200;
\ No newline at end of file
+pub static WINDOW_HEIGHT: u32 = 1200;
+pub static MIN_WINDOW_WIDTH: u32 = 320;
+pub static MIN_WINDOW_HEIGHT: u32 = 240;
+
+// Space around the line numbers in the gutter and the text in the status bar.
+pub static GUTTER_PADDING: u32 = 10;
+pub static STATUS_BAR_PADDING: u32 = 4;
+
+// Characters treated as part of a word by Ctrl+Left/Right, in addition to letters and digits.
+pub static WORD_CHARACTERS: &str = "_";
+// Home jumps to the first non-blank character before the start of the line.
+pub static SMART_HOME: bool = true;
+
+// Minimum number of lines/columns kept between the cursor and the edge of the view.
+pub static SCROLL_OFF_LINES: u32 = 3;
+pub static SCROLL_OFF_COLUMNS: u32 = 8;
+
+// Distance scrolled by one step of the mouse wheel.
+pub static SCROLL_WHEEL_LINES: u32 = 3;
+pub static SCROLL_WHEEL_COLUMNS: u32 = 6;
+
+// When enabled the view eases towards its new position instead of jumping.
+// The speed is the fraction of the remaining distance covered each frame.
+pub static SMOOTH_SCROLLING: bool = true;
+pub static SMOOTH_SCROLL_SPEED: f32 = 0.3;
+
+// Number of result lines shown by the Find in Files panel, and how much of
+// each matching line its preview shows.
+pub static SEARCH_PANEL_LINES: u32 = 12;
+pub static SEARCH_PREVIEW_LENGTH: usize = 120;
+
+// Split panes never shrink below this many pixels, and the divider between
+// them is this thick.
+pub static MIN_PANE_SIZE: u32 = 100;
+pub static DIVIDER_SIZE: u32 = 4;
+
+// Width of the file explorer sidebar.
+pub static SIDEBAR_WIDTH: u32 = 260;
+
+// Size of the Ctrl+P file finder: its width, the number of files listed and
+// the number of lines previewed from the selected one. Overlays like it are
+// drawn this far below the top of the editor.
+pub static FILE_FINDER_WIDTH: u32 = 800;
+pub static FILE_FINDER_LINES: u32 = 10;
+pub static FILE_FINDER_PREVIEW_LINES: u32 = 12;
+// At most this many bytes are read from a file for its preview.
+pub static FILE_FINDER_PREVIEW_BYTES: u64 = 16 * 1024;
+pub static OVERLAY_MARGIN: u32 = 40;
+
+// Size of the Ctrl+Shift+P command palette: its width and the number of commands listed.
+pub static COMMAND_PALETTE_WIDTH: u32 = 700;
+pub static COMMAND_PALETTE_LINES: u32 = 12;
+
+// Start with Vim emulation turned on. It can be toggled from the command palette.
+pub static VIM_MODE: bool = false;
+
+// Typing a bracket or quote adds the closing one.
+pub static AUTO_CLOSE_BRACKETS: bool = true;
+
+// Start with the Emacs keybinding profile instead of the default one.
+pub static EMACS_KEYBINDINGS: bool = false;
+// How many kills are kept for yanking.
+pub static KILL_RING_SIZE: usize = 120;
+
+// Words are completed from the open documents once this many characters of
+// them are typed. The popup shows this many words at a time.
+pub static COMPLETION_MIN_PREFIX: usize = 2;
+pub static COMPLETION_LINES: u32 = 8;
+// Words are taken from this many lines above and below the cursor, and from
+// the whole of the other open documents.
+pub static COMPLETION_SCAN_LINES: usize = 1000;
+
+// How many closed tabs are remembered for reopening with Ctrl+Shift+T.
+pub static CLOSED_DOCUMENTS_LIMIT: usize = 20;
+
+// Directory holding the editor's persisted state, e.g. the last window size and position.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs_next::config_dir().map(|dir| dir.join("text-editor"))
+}
\ No newline at end of file
@@ -0,0 +1,1145 @@
+use std::{collections::HashMap, fmt};
+
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::{
+    clipboard::Clipboard,
+    constants::EOF_CHAR,
+    document::Document,
+    gap_buffer::GapBuffer,
+    indentation,
+    motions::{self, char_class, CharClass},
+    selection::{Selection, Selections},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+// A key as the Vim emulation sees it. Printable keys arrive as text, so they
+// follow the keyboard layout.
+#[derive(Clone, Copy, PartialEq)]
+pub enum VimKey {
+    Char(char),
+    Escape,
+    Enter,
+    Backspace,
+    Delete,
+    Redo,
+}
+
+impl VimKey {
+    // Translates the keys that do not type text. Arrow keys and the like act
+    // as their Vim counterparts, anything else is left to the keymap.
+    pub fn from_keydown(keycode: Keycode, keymod: Mod) -> Option<Self> {
+        if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+            return None;
+        }
+        let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+        match keycode {
+            Keycode::R if ctrl => Some(VimKey::Redo),
+            _ if ctrl => None,
+            Keycode::Escape => Some(VimKey::Escape),
+            Keycode::Return | Keycode::KpEnter => Some(VimKey::Enter),
+            Keycode::Backspace => Some(VimKey::Backspace),
+            Keycode::Left => Some(VimKey::Char('h')),
+            Keycode::Right => Some(VimKey::Char('l')),
+            Keycode::Up => Some(VimKey::Char('k')),
+            Keycode::Down => Some(VimKey::Char('j')),
+            Keycode::Home => Some(VimKey::Char('0')),
+            Keycode::End => Some(VimKey::Char('$')),
+            Keycode::Delete => Some(VimKey::Delete),
+            Keycode::Tab => Some(VimKey::Char('\t')),
+            _ => None,
+        }
+    }
+
+    // Reads a key back from the way it is displayed.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "<Esc>" => Some(VimKey::Escape),
+            "<CR>" => Some(VimKey::Enter),
+            "<BS>" => Some(VimKey::Backspace),
+            "<Del>" => Some(VimKey::Delete),
+            "<C-r>" => Some(VimKey::Redo),
+            _ => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(VimKey::Char(c)),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for VimKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VimKey::Char(c) => write!(f, "{}", c),
+            VimKey::Escape => write!(f, "<Esc>"),
+            VimKey::Enter => write!(f, "<CR>"),
+            VimKey::Backspace => write!(f, "<BS>"),
+            VimKey::Delete => write!(f, "<Del>"),
+            VimKey::Redo => write!(f, "<C-r>"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VimMotion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordEnd,
+    WordBack,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    ParagraphUp,
+    ParagraphDown,
+    // `f`/`t` and `F`/`T`: the character to find on the line, and whether to stop next to it.
+    FindForward(char, bool),
+    FindBackward(char, bool),
+}
+
+// How much of the text between the cursor and the end of a motion an operator covers.
+#[derive(PartialEq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+impl VimMotion {
+    fn kind(self) -> MotionKind {
+        match self {
+            VimMotion::Up | VimMotion::Down | VimMotion::FirstLine | VimMotion::LastLine => MotionKind::Linewise,
+            VimMotion::WordEnd | VimMotion::FindForward(..) => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Motion(VimMotion),
+    // The operator key twice, like `dd`.
+    Lines,
+    // A text object like `iw` or `a"`: whether it is the `a` variant, and the object.
+    Object(bool, char),
+    // The visual selection.
+    Selection,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Move(VimMotion),
+    Operate(Operator, Target),
+    // `i`, `a`, `I`, `A`, `o` or `O`.
+    Insert(char),
+    // `P` puts before the cursor, `p` after it.
+    Put(bool),
+    ReplaceChar(char),
+    Undo,
+    Redo,
+    Repeat,
+    Visual(VimMode),
+    SelectObject(bool, char),
+    SwapEnds,
+}
+
+#[derive(Clone, Copy)]
+struct VimCommand {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+enum Parse<T> {
+    Incomplete,
+    Invalid,
+    Done(T),
+}
+
+impl<T> Parse<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> Parse<U> {
+        match self {
+            Parse::Incomplete => Parse::Incomplete,
+            Parse::Invalid => Parse::Invalid,
+            Parse::Done(value) => Parse::Done(f(value)),
+        }
+    }
+}
+
+// Splits off a leading count. A lone `0` is the motion, not a count.
+fn split_count(keys: &[VimKey]) -> (Option<usize>, &[VimKey]) {
+    let digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|&(i, key)| matches!(key, VimKey::Char(c) if c.is_ascii_digit() && (i > 0 || *c != '0')))
+        .count();
+    let count = keys[..digits]
+        .iter()
+        .filter_map(|key| match key {
+            VimKey::Char(c) => c.to_digit(10),
+            _ => None,
+        })
+        .fold(0usize, |count, digit| count.saturating_mul(10).saturating_add(digit as usize));
+    ((digits > 0).then_some(count), &keys[digits..])
+}
+
+// Counts before and after the operator multiply, like `2d3w`.
+fn multiply(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (None, None) => None,
+        _ => Some(first.unwrap_or(1).saturating_mul(second.unwrap_or(1))),
+    }
+}
+
+fn is_register(name: char) -> bool {
+    name.is_ascii_alphanumeric() || matches!(name, '"' | '+' | '*' | '_')
+}
+
+fn parse_motion(keys: &[VimKey]) -> Parse<VimMotion> {
+    let motion = match keys {
+        [] | [VimKey::Char('g' | 'f' | 't' | 'F' | 'T')] => return Parse::Incomplete,
+        [VimKey::Char('h') | VimKey::Backspace] => VimMotion::Left,
+        [VimKey::Char('l' | ' ')] => VimMotion::Right,
+        [VimKey::Char('j') | VimKey::Enter] => VimMotion::Down,
+        [VimKey::Char('k')] => VimMotion::Up,
+        [VimKey::Char('w' | 'W')] => VimMotion::WordStart,
+        [VimKey::Char('e' | 'E')] => VimMotion::WordEnd,
+        [VimKey::Char('b' | 'B')] => VimMotion::WordBack,
+        [VimKey::Char('0')] => VimMotion::LineStart,
+        [VimKey::Char('^')] => VimMotion::FirstNonBlank,
+        [VimKey::Char('$')] => VimMotion::LineEnd,
+        [VimKey::Char('g'), VimKey::Char('g')] => VimMotion::FirstLine,
+        [VimKey::Char('G')] => VimMotion::LastLine,
+        [VimKey::Char('{')] => VimMotion::ParagraphUp,
+        [VimKey::Char('}')] => VimMotion::ParagraphDown,
+        [VimKey::Char(find @ ('f' | 't' | 'F' | 'T')), VimKey::Char(c)] => {
+            let till = matches!(find, 't' | 'T');
+            if find.is_lowercase() {
+                VimMotion::FindForward(*c, till)
+            } else {
+                VimMotion::FindBackward(*c, till)
+            }
+        }
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(motion)
+}
+
+// Parses what follows an operator: itself again, a text object or a motion.
+fn parse_target(operator_key: char, keys: &[VimKey]) -> Parse<(Option<usize>, Target)> {
+    let (count, keys) = split_count(keys);
+    match keys {
+        [VimKey::Char(c)] if *c == operator_key => Parse::Done((count, Target::Lines)),
+        [VimKey::Char('i' | 'a')] => Parse::Incomplete,
+        [VimKey::Char(kind @ ('i' | 'a')), VimKey::Char(object)] => {
+            Parse::Done((count, Target::Object(*kind == 'a', *object)))
+        }
+        _ => parse_motion(keys).map(|motion| (count, Target::Motion(motion))),
+    }
+}
+
+// Parses the keys typed so far in normal or visual mode into a command, like
+// `"a2dw`: an optional register, a count, and the command itself.
+fn parse_command(keys: &[VimKey], visual: bool) -> Parse<VimCommand> {
+    let (register, keys) = match keys {
+        [VimKey::Char('"')] => return Parse::Incomplete,
+        [VimKey::Char('"'), VimKey::Char(name), rest @ ..] if is_register(*name) => (Some(*name), rest),
+        [VimKey::Char('"'), ..] => return Parse::Invalid,
+        _ => (None, keys),
+    };
+    let (count, keys) = split_count(keys);
+    let command = |count, action| VimCommand { register, count, action };
+    let Some((&key, rest)) = keys.split_first() else {
+        return Parse::Incomplete;
+    };
+
+    let operator = match key {
+        VimKey::Char('d') => Some(Operator::Delete),
+        VimKey::Char('c') => Some(Operator::Change),
+        VimKey::Char('y') => Some(Operator::Yank),
+        VimKey::Char('>') => Some(Operator::Indent),
+        VimKey::Char('<') => Some(Operator::Outdent),
+        _ => None,
+    };
+    if let (Some(operator), VimKey::Char(operator_key)) = (operator, key) {
+        if visual {
+            return Parse::Done(command(count, Action::Operate(operator, Target::Selection)));
+        }
+        return parse_target(operator_key, rest).map(|(target_count, target)| {
+            command(multiply(count, target_count), Action::Operate(operator, target))
+        });
+    }
+
+    let action = if visual {
+        match (key, rest) {
+            (VimKey::Char('x'), []) => Action::Operate(Operator::Delete, Target::Selection),
+            (VimKey::Char('s'), []) => Action::Operate(Operator::Change, Target::Selection),
+            (VimKey::Char('o'), []) => Action::SwapEnds,
+            (VimKey::Char('i' | 'a'), []) => return Parse::Incomplete,
+            (VimKey::Char(kind @ ('i' | 'a')), [VimKey::Char(object)]) => Action::SelectObject(kind == 'a', *object),
+            (VimKey::Char('v'), []) => Action::Visual(VimMode::Visual),
+            (VimKey::Char('V'), []) => Action::Visual(VimMode::VisualLine),
+            _ => return parse_motion(keys).map(|motion| command(count, Action::Move(motion))),
+        }
+    } else {
+        match (key, rest) {
+            (VimKey::Char('x'), []) => Action::Operate(Operator::Delete, Target::Motion(VimMotion::Right)),
+            (VimKey::Char('X'), []) => Action::Operate(Operator::Delete, Target::Motion(VimMotion::Left)),
+            (VimKey::Char('D'), []) => Action::Operate(Operator::Delete, Target::Motion(VimMotion::LineEnd)),
+            (VimKey::Char('C'), []) => Action::Operate(Operator::Change, Target::Motion(VimMotion::LineEnd)),
+            (VimKey::Char('s'), []) => Action::Operate(Operator::Change, Target::Motion(VimMotion::Right)),
+            (VimKey::Char('S'), []) => Action::Operate(Operator::Change, Target::Lines),
+            (VimKey::Char('Y'), []) => Action::Operate(Operator::Yank, Target::Lines),
+            (VimKey::Char(kind @ ('i' | 'a' | 'I' | 'A' | 'o' | 'O')), []) => Action::Insert(kind),
+            (VimKey::Char('p'), []) => Action::Put(false),
+            (VimKey::Char('P'), []) => Action::Put(true),
+            (VimKey::Char('r'), []) => return Parse::Incomplete,
+            (VimKey::Char('r'), [VimKey::Char(c)]) => Action::ReplaceChar(*c),
+            (VimKey::Char('u'), []) => Action::Undo,
+            (VimKey::Redo, []) => Action::Redo,
+            (VimKey::Char('.'), []) => Action::Repeat,
+            (VimKey::Char('v'), []) => Action::Visual(VimMode::Visual),
+            (VimKey::Char('V'), []) => Action::Visual(VimMode::VisualLine),
+            _ => return parse_motion(keys).map(|motion| command(count, Action::Move(motion))),
+        }
+    };
+    Parse::Done(command(count, action))
+}
+
+// Moves to the next blank line after the paragraph the cursor is in, or
+// after the next one if it is between paragraphs.
+fn paragraph_forward(buffer: &GapBuffer, index: usize) -> usize {
+    let text = buffer.to_string();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let (mut row, _) = buffer.get_position_of_index(index);
+    while row < lines.len() && motions::is_blank_line(lines[row]) {
+        row += 1;
+    }
+    while row < lines.len() && !motions::is_blank_line(lines[row]) {
+        row += 1;
+    }
+    if row >= lines.len() {
+        buffer.length() - 1
+    } else {
+        buffer.get_index_at_position(row, 0)
+    }
+}
+
+// Where `motion` takes the cursor from `index`, or None if it cannot move, like
+// `f` without a match. Vertical motions aim for `goal_column`.
+fn motion_target(
+    buffer: &GapBuffer,
+    motion: VimMotion,
+    count: Option<usize>,
+    index: usize,
+    goal_column: usize,
+) -> Option<usize> {
+    let times = count.unwrap_or(1).max(1);
+    let (row, _) = buffer.get_position_of_index(index);
+    let last_row = buffer.get_line_count() - 1;
+    let repeat = |step: fn(&GapBuffer, usize) -> usize| (0..times).fold(index, |index, _| step(buffer, index));
+
+    let target = match motion {
+        VimMotion::Left => index.saturating_sub(times).max(motions::line_start(buffer, index)),
+        VimMotion::Right => (index + times).min(motions::line_end(buffer, index)),
+        VimMotion::Up if row > 0 => buffer.get_index_at_position(row.saturating_sub(times), goal_column),
+        VimMotion::Down if row < last_row => buffer.get_index_at_position((row + times).min(last_row), goal_column),
+        VimMotion::Up | VimMotion::Down => return None,
+        VimMotion::WordStart => repeat(motions::next_word_start),
+        VimMotion::WordEnd => repeat(motions::word_end),
+        VimMotion::WordBack => repeat(motions::word_left),
+        VimMotion::LineStart => motions::line_start(buffer, index),
+        VimMotion::FirstNonBlank => motions::first_non_blank(buffer, index),
+        VimMotion::LineEnd => {
+            let target_row = (row + times - 1).min(last_row);
+            buffer.get_index_at_position(target_row, usize::MAX)
+        }
+        VimMotion::FirstLine | VimMotion::LastLine => {
+            let target_row = match count {
+                Some(line) => line.saturating_sub(1).min(last_row),
+                None if motion == VimMotion::FirstLine => 0,
+                None => last_row,
+            };
+            motions::first_non_blank(buffer, buffer.get_index_at_position(target_row, 0))
+        }
+        VimMotion::ParagraphUp => repeat(motions::paragraph_up),
+        VimMotion::ParagraphDown => repeat(paragraph_forward),
+        VimMotion::FindForward(c, till) => {
+            let found = (index + 1..motions::line_end(buffer, index))
+                .filter(|&i| buffer.get_char(i) == c)
+                .nth(times - 1)?;
+            if till {
+                found - 1
+            } else {
+                found
+            }
+        }
+        VimMotion::FindBackward(c, till) => {
+            let found = (motions::line_start(buffer, index)..index)
+                .rev()
+                .filter(|&i| buffer.get_char(i) == c)
+                .nth(times - 1)?;
+            if till {
+                found + 1
+            } else {
+                found
+            }
+        }
+    };
+    Some(target)
+}
+
+// The range an operator covers with `motion` from `index`, and whether it is
+// made of whole lines.
+fn motion_range(
+    buffer: &GapBuffer,
+    operator: Operator,
+    motion: VimMotion,
+    count: Option<usize>,
+    index: usize,
+) -> Option<(usize, usize, bool)> {
+    // `cw` changes to the end of the word, like `ce`.
+    let on_word = char_class(buffer.get_char(index)) != CharClass::Whitespace;
+    let motion = if operator == Operator::Change && motion == VimMotion::WordStart && on_word {
+        VimMotion::WordEnd
+    } else {
+        motion
+    };
+    let mut target = motion_target(buffer, motion, count, index, 0)?;
+
+    // A word motion that runs into the next line stops at the end of the last word moved over.
+    if motion == VimMotion::WordStart {
+        let times = count.unwrap_or(1).max(1);
+        let previous = if times > 1 {
+            motion_target(buffer, motion, Some(times - 1), index, 0)?
+        } else {
+            index
+        };
+        if buffer.get_position_of_index(target).0 > buffer.get_position_of_index(previous).0 {
+            target = motions::line_end(buffer, previous).max(index);
+        }
+    }
+
+    let (start, end) = (index.min(target), index.max(target));
+    Some(match motion.kind() {
+        MotionKind::Linewise => (start, end, true),
+        MotionKind::Inclusive => (start, (end + 1).min(buffer.length() - 1), false),
+        MotionKind::Exclusive => (start, end, false),
+    })
+}
+
+// The range of a text object around `index`, and whether it is made of whole lines.
+fn object_range(buffer: &GapBuffer, around: bool, object: char, index: usize) -> Option<(usize, usize, bool)> {
+    let chars: Vec<char> = buffer.to_string().chars().collect();
+    let last = buffer.length() - 1;
+    let is_blank = |i: usize| chars[i] == ' ' || chars[i] == '\t';
+
+    match object {
+        'w' | 'W' => {
+            if matches!(chars[index], '\n' | EOF_CHAR) {
+                return None;
+            }
+            // A word, a run of punctuation or a run of blanks.
+            let class = char_class(chars[index]);
+            let same = |i: usize| !matches!(chars[i], '\n' | EOF_CHAR) && char_class(chars[i]) == class;
+            let mut start = index;
+            while start > 0 && same(start - 1) {
+                start -= 1;
+            }
+            let mut end = index + 1;
+            while end < last && same(end) {
+                end += 1;
+            }
+            if around {
+                // The blanks after the word, or before it if there are none after.
+                let mut trailing_end = end;
+                while trailing_end < last && is_blank(trailing_end) {
+                    trailing_end += 1;
+                }
+                if class == CharClass::Whitespace && end < last && !matches!(chars[end], '\n' | EOF_CHAR) {
+                    let next_class = char_class(chars[end]);
+                    while end < last && !matches!(chars[end], '\n' | EOF_CHAR) && char_class(chars[end]) == next_class {
+                        end += 1;
+                    }
+                } else if trailing_end > end {
+                    end = trailing_end;
+                } else {
+                    while start > 0 && is_blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+            Some((start, end, false))
+        }
+        '"' | '\'' | '`' => {
+            // Quotes pair up from the start of the line.
+            let quotes: Vec<usize> = (motions::line_start(buffer, index)..motions::line_end(buffer, index))
+                .filter(|&i| chars[i] == object && (i == 0 || chars[i - 1] != '\\'))
+                .collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(_, close)| index <= close)?;
+            Some(if around { (open, close + 1, false) } else { (open + 1, close, false) })
+        }
+        '(' | ')' | 'b' | '[' | ']' | '{' | '}' | 'B' | '<' | '>' => {
+            let (open_char, close_char) = match object {
+                '(' | ')' | 'b' => ('(', ')'),
+                '[' | ']' => ('[', ']'),
+                '{' | '}' | 'B' => ('{', '}'),
+                _ => ('<', '>'),
+            };
+            // Look outwards for the brackets enclosing the cursor, which may be on either of them.
+            let mut depth = 0;
+            let mut open = None;
+            for i in (0..=index).rev() {
+                if chars[i] == close_char && i != index {
+                    depth += 1;
+                } else if chars[i] == open_char {
+                    if depth == 0 {
+                        open = Some(i);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let open = open?;
+            let mut depth = 0;
+            let mut close = None;
+            for (i, &c) in chars.iter().enumerate().take(last).skip(open + 1) {
+                if c == open_char {
+                    depth += 1;
+                } else if c == close_char {
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let close = close?;
+            if around {
+                return Some((open, close + 1, false));
+            }
+            // Inside a block spread over several lines, keep the lines of the brackets.
+            let mut start = open + 1;
+            let mut end = close;
+            if chars[start] == '\n' && start < end {
+                start += 1;
+                let close_line_start = motions::line_start(buffer, close);
+                if close_line_start >= start && (close_line_start..close).all(is_blank) {
+                    end = close_line_start;
+                }
+            }
+            Some((start, end.max(start), false))
+        }
+        'p' => {
+            // The lines of the paragraph, or of the blank lines, the cursor is on.
+            let text = buffer.to_string();
+            let lines: Vec<&str> = text.split('\n').collect();
+            let (row, _) = buffer.get_position_of_index(index);
+            let blank = motions::is_blank_line(lines[row]);
+            let mut first = row;
+            while first > 0 && motions::is_blank_line(lines[first - 1]) == blank {
+                first -= 1;
+            }
+            let mut last_row = row;
+            while last_row + 1 < lines.len() && motions::is_blank_line(lines[last_row + 1]) == blank {
+                last_row += 1;
+            }
+            if around {
+                while last_row + 1 < lines.len() && motions::is_blank_line(lines[last_row + 1]) != blank {
+                    last_row += 1;
+                }
+            }
+            Some((
+                buffer.get_index_at_position(first, 0),
+                buffer.get_index_at_position(last_row, 0),
+                true,
+            ))
+        }
+        _ => None,
+    }
+}
+
+// In normal mode the cursor sits on a character, never after the last one of a line.
+fn clamp_to_line(buffer: &GapBuffer, index: usize) -> usize {
+    let index = index.min(buffer.length() - 1);
+    if matches!(buffer.get_char(index), '\n' | EOF_CHAR) && index > motions::line_start(buffer, index) {
+        index - 1
+    } else {
+        index
+    }
+}
+
+// Types a key of insert mode the way the editor does when it is typed.
+fn insert_key(document: &mut Document, key: VimKey) {
+    match key {
+        VimKey::Char('\t') => document.insert_tab(),
+        VimKey::Char(c) => document.type_text(&c.to_string()),
+        VimKey::Enter => document.newline(),
+        VimKey::Backspace => document.delete_backward(),
+        VimKey::Delete => document.delete_forward(),
+        _ => {}
+    }
+}
+
+#[derive(Clone, Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+// Vim emulation: normal, insert and visual modes on top of the document's
+// buffer and cursors. Outside insert mode it takes the keys, in insert mode
+// typing goes through the editor as usual and is only recorded for `.`.
+pub struct Vim {
+    mode: VimMode,
+    // Keys of a command that is not complete yet, like `2d`.
+    pending: Vec<VimKey>,
+    registers: HashMap<char, Register>,
+    // The fixed end of the visual selection, and the end the cursor moves.
+    visual_anchor: usize,
+    visual_cursor: usize,
+    // Column kept by `j` and `k` while passing shorter lines.
+    goal_column: Option<usize>,
+    // The last change and what was typed in the insert mode it started, repeated by `.`.
+    last_change: Option<(VimCommand, Vec<VimKey>)>,
+    // The change that started the current insert mode, collecting what is typed.
+    insert_change: Option<(VimCommand, Vec<VimKey>)>,
+    // The undo history before that change, so it undoes together with the typing.
+    insert_checkpoint: usize,
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Vim {
+            mode: VimMode::Normal,
+            pending: Vec::new(),
+            registers: HashMap::new(),
+            visual_anchor: 0,
+            visual_cursor: 0,
+            goal_column: None,
+            last_change: None,
+            insert_change: None,
+            insert_checkpoint: 0,
+        }
+    }
+
+    // The mode indicator for the status bar, with the keys of an incomplete command.
+    pub fn status(&self) -> String {
+        let mode = match self.mode {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "-- INSERT --",
+            VimMode::Visual => "-- VISUAL --",
+            VimMode::VisualLine => "-- VISUAL LINE --",
+        };
+        let pending: String = self.pending.iter().map(|key| key.to_string()).collect();
+        if pending.is_empty() {
+            mode.to_string()
+        } else {
+            format!("{} {}", mode, pending)
+        }
+    }
+
+    // Handles a key that does not type text. Returns false if the editor
+    // should handle it as usual, like everything but Escape in insert mode.
+    pub fn handle_key(&mut self, key: VimKey, document: &mut Document, clipboard: &mut Clipboard) -> bool {
+        if self.mode == VimMode::Insert {
+            match key {
+                VimKey::Escape => {
+                    self.leave_insert(document);
+                    return true;
+                }
+                VimKey::Enter | VimKey::Backspace | VimKey::Delete | VimKey::Char('\t') => self.record_insert(key),
+                _ => {}
+            }
+            return false;
+        }
+        // Delete outside insert mode deletes the character under the cursor.
+        let key = if key == VimKey::Delete { VimKey::Char('x') } else { key };
+        self.feed(key, document, clipboard);
+        true
+    }
+
+    // Handles typed text, which is a command outside insert mode.
+    pub fn handle_text(&mut self, text: &str, document: &mut Document, clipboard: &mut Clipboard) -> bool {
+        if self.mode == VimMode::Insert {
+            for c in text.chars() {
+                self.record_insert(VimKey::Char(c));
+            }
+            return false;
+        }
+        for c in text.chars() {
+            // The rest of the text is typed if a command starts insert mode.
+            if self.mode == VimMode::Insert {
+                self.record_insert(VimKey::Char(c));
+                insert_key(document, VimKey::Char(c));
+            } else {
+                self.feed(VimKey::Char(c), document, clipboard);
+            }
+        }
+        true
+    }
+
+    // Records text the editor typed in insert mode otherwise, like an accepted completion.
+    pub fn record_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.record_insert(VimKey::Char(c));
+        }
+    }
+
+    fn feed(&mut self, key: VimKey, document: &mut Document, clipboard: &mut Clipboard) {
+        self.sync_visual(document);
+        if key == VimKey::Escape {
+            self.pending.clear();
+            if self.mode != VimMode::Normal {
+                self.leave_visual(document);
+            }
+            return;
+        }
+
+        self.pending.push(key);
+        match parse_command(&self.pending, self.mode != VimMode::Normal) {
+            Parse::Incomplete => {}
+            Parse::Invalid => self.pending.clear(),
+            Parse::Done(command) => {
+                self.pending.clear();
+                self.execute(command, document, clipboard);
+            }
+        }
+    }
+
+    fn execute(&mut self, command: VimCommand, document: &mut Document, clipboard: &mut Clipboard) {
+        let checkpoint = document.buffer.undo_checkpoint();
+        let count = command.count.unwrap_or(1).max(1);
+        let visual = matches!(self.mode, VimMode::Visual | VimMode::VisualLine);
+        let cursor = if visual {
+            self.visual_cursor
+        } else {
+            document.buffer.get_cursor()
+        };
+
+        match command.action {
+            Action::Move(motion) => {
+                let (_, column) = document.buffer.get_position_of_index(cursor);
+                let goal_column = self.goal_column.unwrap_or(column);
+                let target = motion_target(&document.buffer, motion, command.count, cursor, goal_column);
+                self.goal_column = match motion {
+                    VimMotion::Up | VimMotion::Down => Some(goal_column),
+                    VimMotion::LineEnd => Some(usize::MAX),
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    self.move_cursor(document, target);
+                }
+            }
+            Action::Operate(operator, target) => {
+                let buffer = &document.buffer;
+                let range = match target {
+                    Target::Motion(motion) => motion_range(buffer, operator, motion, command.count, cursor),
+                    Target::Lines => {
+                        let (row, _) = buffer.get_position_of_index(cursor);
+                        let last_row = (row + count - 1).min(buffer.get_line_count() - 1);
+                        Some((cursor, buffer.get_index_at_position(last_row, 0), true))
+                    }
+                    Target::Object(around, object) => object_range(buffer, around, object, cursor),
+                    Target::Selection => {
+                        let (start, end) = (self.visual_anchor.min(cursor), self.visual_anchor.max(cursor));
+                        if self.mode == VimMode::VisualLine {
+                            Some((start, end, true))
+                        } else {
+                            Some((start, (end + 1).min(buffer.length() - 1), false))
+                        }
+                    }
+                };
+                self.mode = VimMode::Normal;
+                self.goal_column = None;
+                match range {
+                    Some(range) => self.operate(operator, range, cursor, command.register, document, clipboard),
+                    None if visual => self.move_cursor(document, cursor),
+                    None => {}
+                }
+            }
+            Action::Insert(kind) => self.start_insert(kind, document),
+            Action::Put(before) => self.put(before, count, command.register, document, clipboard),
+            Action::ReplaceChar(c) => {
+                let buffer = &mut document.buffer;
+                let end = cursor + count;
+                if end <= motions::line_end(buffer, cursor) {
+                    buffer.begin_undo_group();
+                    buffer.delete_range(cursor, end);
+                    buffer.insert_str(cursor, &c.to_string().repeat(count));
+                    buffer.end_undo_group();
+                    document.has_been_saved = false;
+                    self.move_cursor(document, end - 1);
+                }
+            }
+            Action::Undo | Action::Redo => {
+                for _ in 0..count {
+                    let positions = if command.action == Action::Undo {
+                        document.buffer.undo()
+                    } else {
+                        document.buffer.redo()
+                    };
+                    let Some(position) = positions.and_then(|positions| positions.into_iter().min()) else {
+                        break;
+                    };
+                    document.has_been_saved = false;
+                    self.move_cursor(document, position);
+                }
+            }
+            Action::Repeat => {
+                if let Some((mut change, typed)) = self.last_change.clone() {
+                    // A new count replaces the one the change was made with.
+                    if command.count.is_some() {
+                        change.count = command.count;
+                    }
+                    self.execute(change, document, clipboard);
+                    if self.mode == VimMode::Insert {
+                        for key in typed {
+                            self.record_insert(key);
+                            insert_key(document, key);
+                        }
+                        self.leave_insert(document);
+                    }
+                }
+            }
+            Action::Visual(mode) => {
+                if self.mode == mode {
+                    self.leave_visual(document);
+                } else {
+                    if !visual {
+                        self.visual_anchor = cursor;
+                        self.visual_cursor = cursor;
+                    }
+                    self.mode = mode;
+                    self.select_visual(document);
+                }
+            }
+            Action::SelectObject(around, object) => {
+                if let Some((start, end, linewise)) = object_range(&document.buffer, around, object, cursor) {
+                    self.visual_anchor = start;
+                    if linewise {
+                        self.mode = VimMode::VisualLine;
+                        self.visual_cursor = end;
+                    } else {
+                        self.visual_cursor = end.saturating_sub(1).max(start);
+                    }
+                    self.select_visual(document);
+                }
+            }
+            Action::SwapEnds => {
+                std::mem::swap(&mut self.visual_anchor, &mut self.visual_cursor);
+                self.select_visual(document);
+            }
+        }
+
+        // Remember changes for `.`. Changes made in visual mode are not repeated.
+        let is_change = match command.action {
+            Action::Operate(operator, target) => operator != Operator::Yank && target != Target::Selection,
+            Action::Insert(_) | Action::Put(_) | Action::ReplaceChar(_) => true,
+            _ => false,
+        };
+        if self.mode == VimMode::Insert {
+            self.insert_checkpoint = checkpoint;
+            self.insert_change = is_change.then_some((command, Vec::new()));
+        } else if is_change {
+            self.last_change = Some((command, Vec::new()));
+        }
+    }
+
+    // Deletes, changes, yanks or shifts the text from `start` to `end`, or
+    // the lines they are on if `linewise`.
+    fn operate(
+        &mut self,
+        operator: Operator,
+        (start, end, linewise): (usize, usize, bool),
+        cursor: usize,
+        register: Option<char>,
+        document: &mut Document,
+        clipboard: &mut Clipboard,
+    ) {
+        let buffer = &mut document.buffer;
+        if matches!(operator, Operator::Indent | Operator::Outdent) {
+            // Shifts the lines in the document's indentation style, through a selection over them.
+            let first_line = motions::line_start(buffer, start);
+            let mut lines = Selections::new();
+            lines.set_single(buffer, Selection::range(first_line, motions::line_end(buffer, end)));
+            indentation::shift_lines(buffer, &mut lines, document.indent, operator == Operator::Outdent);
+            document.has_been_saved = false;
+            self.move_cursor(document, motions::first_non_blank(&document.buffer, first_line));
+            return;
+        }
+
+        let last = buffer.length() - 1;
+        let (start, end) = if linewise {
+            (motions::line_start(buffer, start), (motions::line_end(buffer, end) + 1).min(last))
+        } else {
+            (start, end)
+        };
+        let mut text: String = (start..end).map(|i| buffer.get_char(i)).collect();
+        if linewise && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        self.store(register, text, linewise, operator == Operator::Yank, clipboard);
+
+        match operator {
+            Operator::Yank => {
+                // Yanking lines upwards moves the cursor to the first of them.
+                let (start_row, _) = buffer.get_position_of_index(start);
+                let (cursor_row, cursor_column) = buffer.get_position_of_index(cursor);
+                let target = match linewise {
+                    true if start_row < cursor_row => buffer.get_index_at_position(start_row, cursor_column),
+                    true => cursor,
+                    false => start,
+                };
+                self.move_cursor(document, target);
+            }
+            Operator::Delete => {
+                // Deleting the last lines takes the line break before them instead of after.
+                let delete_start = if linewise && end == last && start > 0 { start - 1 } else { start };
+                buffer.delete_range(delete_start, end);
+                document.has_been_saved = false;
+                let target = if linewise {
+                    motions::first_non_blank(&document.buffer, delete_start.min(document.buffer.length() - 1))
+                } else {
+                    start
+                };
+                self.move_cursor(document, target);
+            }
+            Operator::Change => {
+                // Changing lines keeps their indentation and the line break after them.
+                let (start, end) = if linewise {
+                    (motions::first_non_blank(buffer, start), motions::line_end(buffer, end.saturating_sub(1).max(start)))
+                } else {
+                    (start, end)
+                };
+                buffer.delete_range(start, end);
+                document.has_been_saved = false;
+                document.selections.set_single(buffer, Selection::cursor(start));
+                self.mode = VimMode::Insert;
+            }
+            Operator::Indent | Operator::Outdent => {}
+        }
+    }
+
+    fn start_insert(&mut self, kind: char, document: &mut Document) {
+        let buffer = &mut document.buffer;
+        let cursor = buffer.get_cursor();
+        let position = match kind {
+            'a' if !matches!(buffer.get_char(cursor), '\n' | EOF_CHAR) => cursor + 1,
+            'I' => motions::first_non_blank(buffer, cursor),
+            'A' => motions::line_end(buffer, cursor),
+            'o' => {
+                let end = motions::line_end(buffer, cursor);
+                buffer.insert_str(end, "\n");
+                document.has_been_saved = false;
+                end + 1
+            }
+            'O' => {
+                let start = motions::line_start(buffer, cursor);
+                buffer.insert_str(start, "\n");
+                document.has_been_saved = false;
+                start
+            }
+            _ => cursor,
+        };
+        document.selections.set_single(buffer, Selection::cursor(position));
+        self.mode = VimMode::Insert;
+    }
+
+    fn record_insert(&mut self, key: VimKey) {
+        if let Some((_, typed)) = self.insert_change.as_mut() {
+            typed.push(key);
+        }
+    }
+
+    fn leave_insert(&mut self, document: &mut Document) {
+        self.mode = VimMode::Normal;
+        if let Some((command, typed)) = self.insert_change.take() {
+            // A count types the text that many times, like `3ix<Esc>`.
+            if matches!(command.action, Action::Insert('i' | 'a' | 'I' | 'A')) {
+                for _ in 1..command.count.unwrap_or(1) {
+                    for &key in &typed {
+                        insert_key(document, key);
+                    }
+                }
+            }
+            self.last_change = Some((command, typed));
+        }
+        document.buffer.join_undo_steps(self.insert_checkpoint);
+
+        // The cursor steps back onto the last typed character.
+        let cursor = document.buffer.get_cursor();
+        let target = if cursor > motions::line_start(&document.buffer, cursor) {
+            cursor - 1
+        } else {
+            cursor
+        };
+        self.move_cursor(document, target);
+    }
+
+    fn put(
+        &mut self,
+        before: bool,
+        count: usize,
+        register: Option<char>,
+        document: &mut Document,
+        clipboard: &mut Clipboard,
+    ) {
+        let Some(register) = self.register(register, clipboard) else {
+            return;
+        };
+        let text = register.text.repeat(count);
+        if text.is_empty() {
+            return;
+        }
+        let buffer = &mut document.buffer;
+        let cursor = buffer.get_cursor();
+
+        let target = if register.linewise {
+            // Lines go above or below the cursor line.
+            let (row, _) = buffer.get_position_of_index(cursor);
+            if before {
+                let start = motions::line_start(buffer, cursor);
+                buffer.insert_str(start, &text);
+                motions::first_non_blank(buffer, start)
+            } else if row + 1 < buffer.get_line_count() {
+                let start = buffer.get_index_at_position(row + 1, 0);
+                buffer.insert_str(start, &text);
+                motions::first_non_blank(buffer, start)
+            } else {
+                let end = buffer.length() - 1;
+                buffer.insert_str(end, &format!("\n{}", text.strip_suffix('\n').unwrap_or(&text)));
+                motions::first_non_blank(buffer, end + 1)
+            }
+        } else {
+            let at_line_end = matches!(buffer.get_char(cursor), '\n' | EOF_CHAR);
+            let position = if before || at_line_end { cursor } else { cursor + 1 };
+            buffer.insert_str(position, &text);
+            position + text.chars().count() - 1
+        };
+        document.has_been_saved = false;
+        self.move_cursor(document, target);
+    }
+
+    // Stores deleted or yanked text in the register the command names, and in
+    // the unnamed register `"`. Yanks also go to `0`, uppercase names append
+    // to their register, `+` is the system clipboard and `_` discards the text.
+    fn store(&mut self, name: Option<char>, text: String, linewise: bool, yank: bool, clipboard: &mut Clipboard) {
+        let register = Register { text, linewise };
+        let register = match name {
+            Some('_') => return,
+            Some('+' | '*') => {
+                clipboard.set_text(&register.text);
+                register
+            }
+            Some(name) if name.is_ascii_uppercase() => {
+                let appended = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                appended.text.push_str(&register.text);
+                appended.linewise |= register.linewise;
+                appended.clone()
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, register.clone());
+                register
+            }
+            _ => {
+                if yank {
+                    self.registers.insert('0', register.clone());
+                }
+                register
+            }
+        };
+        self.registers.insert('"', register);
+    }
+
+    fn register(&self, name: Option<char>, clipboard: &Clipboard) -> Option<Register> {
+        match name.unwrap_or('"') {
+            '+' | '*' => clipboard.text().map(|text| Register {
+                linewise: text.ends_with('\n'),
+                text,
+            }),
+            name => self.registers.get(&name.to_ascii_lowercase()).cloned(),
+        }
+    }
+
+    // Moves the cursor, or the moving end of the visual selection.
+    fn move_cursor(&mut self, document: &mut Document, index: usize) {
+        match self.mode {
+            VimMode::Visual | VimMode::VisualLine => {
+                self.visual_cursor = index.min(document.buffer.length() - 1);
+                self.select_visual(document);
+            }
+            _ => {
+                let index = clamp_to_line(&document.buffer, index);
+                document.selections.set_single(&mut document.buffer, Selection::cursor(index));
+            }
+        }
+    }
+
+    // The selection shown for visual mode, which includes the character under
+    // the cursor, or every line between the ends for visual line mode.
+    fn visual_selection(&self, buffer: &GapBuffer) -> Selection {
+        let last = buffer.length() - 1;
+        let (anchor, cursor) = (self.visual_anchor.min(last), self.visual_cursor.min(last));
+        let (anchor, head) = match self.mode {
+            VimMode::VisualLine if cursor < anchor => {
+                (motions::line_end(buffer, anchor) + 1, motions::line_start(buffer, cursor))
+            }
+            VimMode::VisualLine => (motions::line_start(buffer, anchor), motions::line_end(buffer, cursor) + 1),
+            _ if cursor < anchor => (anchor + 1, cursor),
+            _ => (anchor, cursor + 1),
+        };
+        Selection::range(anchor.min(last), head.min(last))
+    }
+
+    fn select_visual(&self, document: &mut Document) {
+        let selection = self.visual_selection(&document.buffer);
+        document.selections.set_single(&mut document.buffer, selection);
+    }
+
+    fn leave_visual(&mut self, document: &mut Document) {
+        self.mode = VimMode::Normal;
+        self.move_cursor(document, self.visual_cursor);
+    }
+
+    // Drops out of visual mode if the selection was changed some other way,
+    // e.g. with the mouse or in another tab.
+    fn sync_visual(&mut self, document: &mut Document) {
+        if !matches!(self.mode, VimMode::Visual | VimMode::VisualLine) {
+            return;
+        }
+        let expected = self.visual_selection(&document.buffer);
+        let primary = document.selections.primary();
+        if document.selections.count() > 1 || (primary.anchor, primary.head) != (expected.anchor, expected.head) {
+            self.mode = VimMode::Normal;
+            self.pending.clear();
+            self.move_cursor(document, primary.head);
+        }
+    }
+}